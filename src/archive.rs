@@ -0,0 +1,208 @@
+/*!
+  This module contains the utilities to read configs
+  straight from a zip archive, without extracting it,
+  and to back up the configs of a prefix into one.
+*/
+
+use serde::de::DeserializeOwned;
+use std::{
+    fs, io,
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+use crate::{core::get_config_dir, error::Error, format::Format, profile};
+
+/// Load the `entry` of the zip `archive` into `T`,
+/// the format is detected from the entry extension.
+///
+/// Returns `Error::EntryNotFound` if the archive has no such entry.
+pub fn load_from_archive<T>(archive: impl AsRef<Path>, entry: &str) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let format = Format::for_path(Path::new(entry))?;
+    let mut zip = zip::ZipArchive::new(fs::File::open(archive)?)?;
+
+    let mut file = match zip.by_name(entry) {
+        Ok(file) => file,
+        Err(zip::result::ZipError::FileNotFound) => {
+            return Err(Error::EntryNotFound(entry.to_string()))
+        }
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut content = String::new();
+    file.read_to_string(&mut content)?;
+
+    format.parse(&content)
+}
+
+/// Bundle the config files of `prefix` into the zip archive `dest`, i.e. the
+/// files of its config directory (the base config) along with every profile
+/// (see [`crate::profile`]), keeping their path relative to that directory
+///
+/// Other sub-directories aren't exported. Returns `Error::FileNotFound` if the
+/// config directory doesn't exist, restore the archive with [`import_all_profiles`].
+pub fn export_all_profiles(prefix: &str, dest: impl AsRef<Path>) -> crate::Result<()> {
+    let dir = get_config_dir(prefix)
+        .filter(|dir| dir.is_dir())
+        .ok_or(Error::FileNotFound)?;
+
+    let mut files = dir_files(&dir, "")?;
+    if let Some(profiles) = profile::profiles_dir(prefix) {
+        files.extend(dir_files(&profiles, "profiles/")?);
+    }
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(dest)?);
+    let options = zip::write::SimpleFileOptions::default();
+
+    for (entry, path) in files {
+        zip.start_file(entry, options)?;
+        io::copy(&mut fs::File::open(path)?, &mut zip)?;
+    }
+
+    zip.finish()?;
+
+    Ok(())
+}
+
+/// Restore the config files of `prefix` from an archive written by
+/// [`export_all_profiles`], overwriting the existing ones
+///
+/// Entries which would be extracted outside of the config directory
+/// fail with `Error::InvalidConfig`.
+pub fn import_all_profiles(prefix: &str, src: impl AsRef<Path>) -> crate::Result<()> {
+    let dir = get_config_dir(prefix).ok_or(Error::Custom("Could not create file"))?;
+    let mut zip = zip::ZipArchive::new(fs::File::open(src)?)?;
+
+    for idx in 0..zip.len() {
+        let mut file = zip.by_index(idx)?;
+        if file.is_dir() {
+            continue;
+        }
+
+        let path = file
+            .enclosed_name()
+            .map(|name| dir.join(name))
+            .ok_or(Error::InvalidConfig(
+                "archive entry outside of the config directory",
+            ))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        io::copy(&mut file, &mut fs::File::create(&path)?)?;
+    }
+
+    Ok(())
+}
+
+/// List the files directly in `dir` along with their entry name (`{prefix}{name}`),
+/// sorted by name. A missing `dir` has no files.
+fn dir_files(dir: &Path, prefix: &str) -> crate::Result<Vec<(String, PathBuf)>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::FileSystem(err)),
+    };
+
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() {
+            continue;
+        }
+
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or(Error::InvalidConfig("non UTF-8 file name"))?;
+
+        files.push((format!("{prefix}{name}"), path));
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestConfig;
+    use std::io::Write;
+    use tempdir::TempDir;
+
+    fn write_archive(path: &Path) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+
+        zip.start_file("configs/app.json", options).unwrap();
+        zip.write_all(br#"{ "foo": "zipped", "bar": false, "baz": 7 }"#)
+            .unwrap();
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_load_from_archive() {
+        let dir = TempDir::new("test_config").unwrap();
+        let archive = dir.path().join("bundle.zip");
+        write_archive(&archive);
+
+        let config: TestConfig = load_from_archive(&archive, "configs/app.json").unwrap();
+        assert_eq!(config.foo, "zipped");
+        assert_eq!(config.baz, 7);
+    }
+
+    #[test]
+    fn test_load_missing_entry() {
+        let dir = TempDir::new("test_config").unwrap();
+        let archive = dir.path().join("bundle.zip");
+        write_archive(&archive);
+
+        let err = load_from_archive::<TestConfig>(&archive, "missing.json").unwrap_err();
+        assert!(matches!(&err, Error::EntryNotFound(entry) if entry == "missing.json"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_export_import_all_profiles() {
+        let home = crate::test_utils::TempHome::new();
+        let dir = TempDir::new("test_backup").unwrap();
+        let archive = dir.path().join("backup.zip");
+
+        let base = crate::init(TestConfig::default(), "my-app", "config.json").unwrap();
+        let dev = TestConfig {
+            foo: "dev".to_string(),
+            ..TestConfig::default()
+        };
+        profile::save_profile(&dev, "my-app", "dev").unwrap();
+        profile::save_profile(&TestConfig::default(), "my-app", "prod").unwrap();
+
+        export_all_profiles("my-app", &archive).unwrap();
+
+        fs::remove_dir_all(home.config_dir().join("my-app")).unwrap();
+        assert!(profile::list_profiles("my-app").unwrap().is_empty());
+
+        import_all_profiles("my-app", &archive).unwrap();
+
+        assert_eq!(
+            <TestConfig as crate::fs::File>::load(&base).unwrap(),
+            TestConfig::default()
+        );
+        assert_eq!(profile::list_profiles("my-app").unwrap(), ["dev", "prod"]);
+        assert_eq!(
+            profile::load_profile::<TestConfig>("my-app", "dev").unwrap(),
+            dev
+        );
+
+        assert!(matches!(
+            export_all_profiles("other-app", &archive),
+            Err(Error::FileNotFound)
+        ));
+    }
+}
@@ -0,0 +1,217 @@
+/*!
+  This module contains a ready-made `config` subcommand
+  for the CLIs built with `clap`.
+*/
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{io::Write, path::PathBuf};
+
+use crate::{error::Error, format::Format, fs::File, value};
+
+/// The `config get/set/edit/show/path/reset` subcommands,
+/// run with [`run_config_command`]
+///
+/// ```
+/// use cli_config::command::ConfigCommand;
+///
+/// #[derive(clap::Parser)]
+/// enum Cli {
+///     /// Manage the configuration
+///     #[command(subcommand)]
+///     Config(ConfigCommand),
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, clap::Subcommand)]
+pub enum ConfigCommand {
+    /// Print the value of a key (e.g. `server.port`)
+    Get {
+        /// Dotted path of the key
+        key: String,
+    },
+    /// Set the value of a key (e.g. `server.port 8080`)
+    Set {
+        /// Dotted path of the key
+        key: String,
+        /// New value, parsed as JSON unless the key holds a string
+        value: String,
+    },
+    /// Open the config in `$VISUAL`/`$EDITOR`
+    Edit,
+    /// Print the whole config
+    Show,
+    /// Print the path of the config file
+    Path,
+    /// Restore the default config
+    Reset {
+        /// Keep a copy of the current config
+        #[arg(long)]
+        backup: bool,
+    },
+}
+
+/// Run `cmd` against the config `filename` of `prefix`, printing to stdout
+///
+/// `set` and `edit` (see [`crate::edit_config`]) create the config when none
+/// exists, `get` and `show` fail with `Error::FileNotFound`.
+pub fn run_config_command<T>(cmd: ConfigCommand, prefix: &str, filename: &str) -> crate::Result<()>
+where
+    T: Serialize + DeserializeOwned + Default + File,
+{
+    run_config_command_to::<T>(cmd, prefix, filename, &mut std::io::stdout().lock())
+}
+
+/// Same as [`run_config_command`] but the output is written to `out`
+pub fn run_config_command_to<T>(
+    cmd: ConfigCommand,
+    prefix: &str,
+    filename: &str,
+    out: &mut impl Write,
+) -> crate::Result<()>
+where
+    T: Serialize + DeserializeOwned + Default + File,
+{
+    match cmd {
+        ConfigCommand::Get { key } => {
+            let config = serde_json::to_value(load::<T>(prefix, filename)?.1)?;
+
+            match value::get_path(&config, &key) {
+                Some(Value::String(value)) => writeln!(out, "{value}")?,
+                Some(value) => writeln!(out, "{value}")?,
+                None => return Err(Error::KeyNotFound(key)),
+            }
+        }
+        ConfigCommand::Set { key, value: raw } => {
+            let path = crate::init(T::default(), prefix, filename)?;
+            let mut config = serde_json::to_value(T::load(&path)?)?;
+
+            let value = match value::get_path(&config, &key) {
+                Some(Value::String(_)) => Value::String(raw),
+                _ => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+            };
+
+            value::set_path(&mut config, &key, value)?;
+            // checks the new value against `T`
+            value::from_value::<T>(config)?.write(&path)?;
+        }
+        ConfigCommand::Edit => crate::edit_config::<T>(prefix, filename)?,
+        ConfigCommand::Show => {
+            let (path, config) = load::<T>(prefix, filename)?;
+            let format = Format::for_path(&path)?;
+
+            writeln!(out, "{}", config.to_pretty_string(format)?)?;
+        }
+        ConfigCommand::Path => {
+            let path = match crate::locate_config(prefix, filename) {
+                Some(path) => path,
+                None => crate::core::get_new_config_path(prefix, filename)?
+                    .ok_or(Error::Custom("Could not create file"))?,
+            };
+
+            writeln!(out, "{}", path.display())?;
+        }
+        ConfigCommand::Reset { backup } => {
+            let path = crate::reset_to_default::<T>(prefix, filename, backup)?;
+
+            writeln!(out, "{}", path.display())?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Load the config located by [`crate::locate_config`] along with its path
+fn load<T>(prefix: &str, filename: &str) -> crate::Result<(PathBuf, T)>
+where
+    T: File,
+{
+    let path = crate::locate_config(prefix, filename).ok_or(Error::FileNotFound)?;
+    let config = T::load(&path).map_err(|err| err.in_file(&path))?;
+
+    Ok((path, config))
+}
+
+#[cfg(all(test, feature = "json", not(windows)))]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TempHome, TestConfig};
+
+    fn run(cmd: ConfigCommand) -> crate::Result<String> {
+        let mut out = Vec::new();
+        run_config_command_to::<TestConfig>(cmd, "my-app", "config.json", &mut out)?;
+
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn test_get_set() {
+        let home = TempHome::new();
+
+        assert!(matches!(
+            run(ConfigCommand::Get {
+                key: "foo".to_string()
+            }),
+            Err(Error::FileNotFound)
+        ));
+
+        // the config is created on the first `set`
+        run(ConfigCommand::Set {
+            key: "baz".to_string(),
+            value: "7".to_string(),
+        })
+        .unwrap();
+        run(ConfigCommand::Set {
+            key: "foo".to_string(),
+            value: "42".to_string(),
+        })
+        .unwrap();
+
+        let path = home.config_dir().join("my-app/config.json");
+        let config = TestConfig::load(&path).unwrap();
+        assert_eq!(config.foo, "42");
+        assert_eq!(config.baz, 7);
+
+        let get = |key: &str| {
+            run(ConfigCommand::Get {
+                key: key.to_string(),
+            })
+        };
+        assert_eq!(get("foo").unwrap(), "42\n");
+        assert_eq!(get("baz").unwrap(), "7\n");
+        assert_eq!(get("bar").unwrap(), "true\n");
+        assert!(matches!(get("qux"), Err(Error::KeyNotFound(key)) if key == "qux"));
+
+        // the value must match the type of the field
+        assert!(run(ConfigCommand::Set {
+            key: "bar".to_string(),
+            value: "yes".to_string(),
+        })
+        .is_err());
+        assert!(TestConfig::load(&path).unwrap().bar);
+
+        assert_eq!(
+            run(ConfigCommand::Path).unwrap(),
+            format!("{}\n", path.display())
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        use clap::Parser;
+
+        #[derive(Parser)]
+        enum Cli {
+            #[command(subcommand)]
+            Config(ConfigCommand),
+        }
+
+        let Cli::Config(cmd) = Cli::parse_from(["app", "config", "set", "server.port", "8080"]);
+        assert_eq!(
+            cmd,
+            ConfigCommand::Set {
+                key: "server.port".to_string(),
+                value: "8080".to_string(),
+            }
+        );
+    }
+}
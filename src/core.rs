@@ -1,27 +1,274 @@
 use home::home_dir;
-use std::path::PathBuf;
+use std::fs::OpenOptions;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
+use crate::format::Format;
 use crate::fs::File;
 
+/// The `xdg::BaseDirectories` of each prefix, see [`base_directories`]
+#[cfg(not(windows))]
+static BASE_DIRECTORIES: std::sync::OnceLock<
+    std::sync::Mutex<std::collections::HashMap<String, Option<xdg::BaseDirectories>>>,
+> = std::sync::OnceLock::new();
+
+/// Number of `xdg::BaseDirectories` actually computed (i.e. cache misses)
+#[cfg(all(test, not(windows)))]
+static BASE_DIRECTORIES_COMPUTED: std::sync::atomic::AtomicUsize =
+    std::sync::atomic::AtomicUsize::new(0);
+
+/// Get the (cached) `xdg::BaseDirectories` of `prefix`, an empty `prefix`
+/// meaning no prefix at all.
+///
+/// Returns `None` for a `prefix` which could escape the config directory
+/// (see [`is_plain_name`]).
+///
+/// The environment is only read the first time a prefix is requested,
+/// call [`reset_config_cache`] after changing it.
+#[cfg(not(windows))]
+fn base_directories(prefix: &str) -> Option<xdg::BaseDirectories> {
+    if !prefix.is_empty() && !is_plain_name(prefix) {
+        return None;
+    }
+
+    let cache = BASE_DIRECTORIES.get_or_init(Default::default);
+    let mut cache = cache.lock().unwrap_or_else(|err| err.into_inner());
+
+    cache
+        .entry(prefix.to_string())
+        .or_insert_with(|| {
+            #[cfg(test)]
+            BASE_DIRECTORIES_COMPUTED.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+
+            xdg::BaseDirectories::with_prefix(prefix).ok()
+        })
+        .clone()
+}
+
+/// Forget the config directories cached by the discovery functions,
+/// e.g. after changing `$HOME` or `$XDG_CONFIG_HOME`
+pub fn reset_config_cache() {
+    #[cfg(not(windows))]
+    if let Some(cache) = BASE_DIRECTORIES.get() {
+        cache.lock().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+}
+
 /// Get the path for creating a new config file
 /// trying to use `$XDG_CONFIG_HOME/{prefix}/{filename}`
 ///
 /// - `prefix` is the name of the folder that will contain the config file
+///
+/// Returns `None` when no config directory can be determined.
 #[cfg(not(windows))]
-fn get_new_config_path(prefix: &str, filename: &str) -> Option<PathBuf> {
-    xdg::BaseDirectories::with_prefix(prefix)
-        .ok()
-        .and_then(|base| base.place_config_file(filename).ok())
+pub(crate) fn get_new_config_path(prefix: &str, filename: &str) -> crate::Result<Option<PathBuf>> {
+    validate_names(prefix, filename)?;
+    check_config_dir(prefix)?;
+
+    match base_directories(prefix) {
+        Some(base) => Ok(Some(base.place_config_file(filename)?)),
+        None => Ok(None),
+    }
 }
 
 /// Get the path for creating a new config file on windows
 ///
 /// - `prefix` is the name of the folder that will contain the config file
 #[cfg(windows)]
-fn get_new_config_path(prefix: &str, filename: &str) -> Option<PathBuf> {
-    dirs::config_dir()
-        .map(|p| p.join(&format!("{}\\{}", prefix, filename)))
-        .find(|p| p.exists())
+pub(crate) fn get_new_config_path(prefix: &str, filename: &str) -> crate::Result<Option<PathBuf>> {
+    validate_names(prefix, filename)?;
+    check_config_dir(prefix)?;
+
+    match dirs::config_dir() {
+        Some(dir) => {
+            let dir = extended_length(&dir.join(prefix));
+            std::fs::create_dir_all(&dir)?;
+            Ok(Some(extended_length(&dir.join(filename))))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Longest path accepted by the Windows APIs without the `\\?\` prefix
+#[cfg(windows)]
+const MAX_PATH: usize = 260;
+
+/// Add the verbatim `\\?\` prefix (`\\?\UNC\` for network shares) to an
+/// absolute `path` too long for the Windows APIs, other paths are kept as-is.
+///
+/// The path must be normalized: verbatim paths aren't (no `/`, `.` nor `..`).
+#[cfg(windows)]
+fn extended_length(path: &Path) -> PathBuf {
+    use std::path::{Component, Prefix};
+
+    if path.as_os_str().len() < MAX_PATH {
+        return path.to_path_buf();
+    }
+
+    let Some(Component::Prefix(prefix)) = path.components().next() else {
+        return path.to_path_buf();
+    };
+
+    let mut extended = std::ffi::OsString::new();
+
+    match prefix.kind() {
+        Prefix::Disk(_) => {
+            extended.push(r"\\?\");
+            extended.push(path);
+        }
+        Prefix::UNC(server, share) => {
+            extended.push(r"\\?\UNC\");
+            extended.push(server);
+            extended.push(r"\");
+            extended.push(share);
+            extended.push(path.components().skip(1).collect::<PathBuf>());
+        }
+        // already verbatim, or a device path
+        _ => return path.to_path_buf(),
+    }
+
+    PathBuf::from(extended)
+}
+
+/// Get the directory holding the config files of `prefix`
+/// (`$XDG_CONFIG_HOME/{prefix}`)
+///
+/// Returns `None` for an invalid `prefix`, see [`is_plain_name`].
+#[cfg(not(windows))]
+pub(crate) fn get_config_dir(prefix: &str) -> Option<PathBuf> {
+    if !is_plain_name(prefix) {
+        return None;
+    }
+
+    base_directories(prefix).map(|base| base.get_config_home())
+}
+
+/// Get the directory holding the config files of `prefix` on windows
+///
+/// Returns `None` for an invalid `prefix`, see [`is_plain_name`].
+#[cfg(windows)]
+pub(crate) fn get_config_dir(prefix: &str) -> Option<PathBuf> {
+    if !is_plain_name(prefix) {
+        return None;
+    }
+
+    dirs::config_dir().map(|p| p.join(prefix))
+}
+
+/// Whether `name` is a single path component (no separator, `.` nor `..`)
+pub(crate) fn is_plain_name(name: &str) -> bool {
+    !matches!(name, "" | "." | "..") && !name.contains(['/', '\\', '\0'])
+}
+
+/// Reject a `prefix` or `filename` which could escape the config directory,
+/// e.g. when they are built from untrusted input
+fn validate_names(prefix: &str, filename: &str) -> crate::Result<()> {
+    if !is_plain_name(prefix) {
+        return Err(crate::error::Error::InvalidConfig("invalid prefix"));
+    }
+
+    if !is_plain_name(filename) {
+        return Err(crate::error::Error::InvalidConfig("invalid filename"));
+    }
+
+    Ok(())
+}
+
+/// Fail with `Error::PermissionDenied` when the config directory of `prefix`
+/// exists but cannot be read, instead of treating it as missing
+fn check_config_dir(prefix: &str) -> crate::Result<()> {
+    let Some(dir) = get_config_dir(prefix) else {
+        return Ok(());
+    };
+
+    match std::fs::read_dir(&dir) {
+        Err(err) if err.kind() == io::ErrorKind::PermissionDenied => {
+            Err(crate::error::Error::PermissionDenied(dir))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// A config filename depending on the platform, e.g. `settings.ini` on Windows
+/// and `.myapprc` elsewhere
+///
+/// It dereferences to the name of the current platform so that it can be
+/// given to [`locate_config`], [`init`] and the like:
+///
+/// ```
+/// use cli_config::PlatformFilename;
+///
+/// const FILENAME: PlatformFilename = PlatformFilename {
+///     windows: "settings.ini",
+///     unix: ".myapprc",
+/// };
+///
+/// let path = cli_config::locate_config("my-app", &FILENAME);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlatformFilename {
+    /// Name used on Windows
+    pub windows: &'static str,
+    /// Name used on every other platform
+    pub unix: &'static str,
+}
+
+impl PlatformFilename {
+    /// The name of the current platform
+    pub const fn current(&self) -> &'static str {
+        if cfg!(windows) {
+            self.windows
+        } else {
+            self.unix
+        }
+    }
+}
+
+impl std::ops::Deref for PlatformFilename {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        self.current()
+    }
+}
+
+impl std::fmt::Display for PlatformFilename {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.current())
+    }
+}
+
+/// Where a config file has been found
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Provided explicitly, e.g. through a `--config` command-line flag
+    Explicit,
+    /// `$XDG_CONFIG_HOME/{prefix}/{filename}`
+    XdgConfig,
+    /// `$XDG_CONFIG_HOME/{prefix}.json`
+    XdgConfigFallback,
+    /// `$HOME/.config/{prefix}/{filename}`
+    HomeConfig,
+    /// `$HOME/.{prefix}.json`
+    HomeDotfile,
+    /// The platform config directory (`%APPDATA%` on windows)
+    PlatformConfig,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let source = match self {
+            ConfigSource::Explicit => "command-line --config flag",
+            ConfigSource::XdgConfig => "$XDG_CONFIG_HOME/{prefix}/{filename}",
+            ConfigSource::XdgConfigFallback => "$XDG_CONFIG_HOME/{prefix}.json",
+            ConfigSource::HomeConfig => "$HOME/.config/{prefix}/{filename}",
+            ConfigSource::HomeDotfile => "$HOME/.{prefix}.json",
+            ConfigSource::PlatformConfig => "platform config directory",
+        };
+
+        f.write_str(source)
+    }
 }
 
 /// Try to find the location of the first config file in the following paths:
@@ -30,33 +277,72 @@ fn get_new_config_path(prefix: &str, filename: &str) -> Option<PathBuf> {
 /// 2. $XDG_CONFIG_HOME/{prefix}.json
 /// 3. $HOME/.config/{prefix}/{filename}
 /// 4. $HOME/.{prefix}
-#[cfg(not(windows))]
+///
+/// A `.yml` filename also matches a `.yaml` file (and vice versa).
+///
+/// `prefix` and `filename` must be plain names: nothing is found (and nothing
+/// can be created) when they contain a path separator or are `.`/`..`.
+///
+/// The XDG directories are resolved once per prefix, see [`reset_config_cache`].
 pub fn locate_config(prefix: &str, filename: &str) -> Option<PathBuf> {
-    xdg::BaseDirectories::with_prefix(prefix)
-        .ok()
+    locate_config_with_source(prefix, filename).map(|(path, _)| path)
+}
+
+/// The spellings of `filename` to look for: `.yml` and `.yaml` are equivalent
+/// (the given one is tried first)
+fn filename_variants(filename: &str) -> Vec<String> {
+    let mut variants = vec![filename.to_string()];
+    let path = Path::new(filename);
+
+    let alternative = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) if ext.eq_ignore_ascii_case("yml") => "yaml",
+        Some(ext) if ext.eq_ignore_ascii_case("yaml") => "yml",
+        _ => return variants,
+    };
+
+    variants.push(
+        path.with_extension(alternative)
+            .to_string_lossy()
+            .into_owned(),
+    );
+    variants
+}
+
+/// Same as [`locate_config`] but also reports where the config has been found
+#[cfg(not(windows))]
+pub fn locate_config_with_source(prefix: &str, filename: &str) -> Option<(PathBuf, ConfigSource)> {
+    validate_names(prefix, filename).ok()?;
+
+    let filenames = filename_variants(filename);
+
+    base_directories(prefix)
         // Search for case n. 1
-        .and_then(|xdg| xdg.find_config_file(filename))
+        .and_then(|xdg| filenames.iter().find_map(|name| xdg.find_config_file(name)))
+        .map(|path| (path, ConfigSource::XdgConfig))
         .or_else(|| {
-            xdg::BaseDirectories::new()
-                .ok()
+            base_directories("")
                 // Search for case n. 2
                 .and_then(|fallback| fallback.find_config_file(format!("{prefix}.json")))
+                .map(|path| (path, ConfigSource::XdgConfigFallback))
         })
         .or_else(|| {
             if let Some(home_path) = home_dir() {
                 // Search for case n. 3 ($HOME/.config/{prefix}/{filename})
-                let fallback_path = format!(".config/{prefix}");
-                let fallback = home_path.join(fallback_path).join(filename);
+                let fallback_dir = home_path.join(format!(".config/{prefix}"));
 
-                if fallback.exists() {
-                    return Some(fallback);
+                for name in &filenames {
+                    let fallback = fallback_dir.join(name);
+
+                    if fallback.exists() {
+                        return Some((fallback, ConfigSource::HomeConfig));
+                    }
                 }
 
                 // Search for case n. 4 ($HOME/.{prefix})
                 let fallback = home_path.join(format!(".{prefix}.json"));
 
                 if fallback.exists() {
-                    return Some(fallback);
+                    return Some((fallback, ConfigSource::HomeDotfile));
                 }
             }
 
@@ -66,10 +352,213 @@ pub fn locate_config(prefix: &str, filename: &str) -> Option<PathBuf> {
 
 /// Get the location of the config file on windows
 #[cfg(windows)]
-pub fn locate_config(prefix: &str, filename: &str) -> Option<PathBuf> {
-    dirs::config_dir()
-        .map(|p| p.join(&format!("{}\\{}", prefix, filename)))
-        .filter(|p| p.exists())
+pub fn locate_config_with_source(prefix: &str, filename: &str) -> Option<(PathBuf, ConfigSource)> {
+    validate_names(prefix, filename).ok()?;
+
+    let dir = dirs::config_dir()?.join(prefix);
+
+    filename_variants(filename)
+        .into_iter()
+        .map(|name| extended_length(&dir.join(name)))
+        .find(|p| p.exists())
+        .map(|path| (path, ConfigSource::PlatformConfig))
+}
+
+/// Same as [`locate_config`] but a misconfigured environment is reported
+/// instead of being treated as "not found":
+///
+/// - an invalid `prefix` or `filename` fails with `Error::InvalidConfig`
+/// - a relative `$XDG_CONFIG_HOME`, ignored by [`locate_config`] as per the
+///   XDG spec, fails with `Error::InvalidXdgConfigHome`
+/// - the XDG directories failing to resolve (e.g. no home directory) fails
+///   with `Error::BaseDirectories`
+///
+/// Returns `Ok(None)` when the environment is valid but no config exists.
+#[cfg(not(windows))]
+pub fn locate_config_strict(prefix: &str, filename: &str) -> crate::Result<Option<PathBuf>> {
+    validate_names(prefix, filename)?;
+
+    if let Some(dir) = std::env::var_os("XDG_CONFIG_HOME") {
+        let dir = PathBuf::from(dir);
+
+        // an empty variable is equivalent to an unset one
+        if !dir.as_os_str().is_empty() && !dir.is_absolute() {
+            return Err(crate::error::Error::InvalidXdgConfigHome(dir));
+        }
+    }
+
+    // not cached, to report the error
+    xdg::BaseDirectories::with_prefix(prefix)?;
+
+    Ok(locate_config(prefix, filename))
+}
+
+/// Same as [`locate_config`] but an invalid `prefix` or `filename` fails
+/// with `Error::InvalidConfig`
+#[cfg(windows)]
+pub fn locate_config_strict(prefix: &str, filename: &str) -> crate::Result<Option<PathBuf>> {
+    validate_names(prefix, filename)?;
+
+    Ok(locate_config(prefix, filename))
+}
+
+/// Find the config strictly at `$HOME/.config/{prefix}/{filename}`
+///
+/// Unlike [`locate_config`], `$XDG_CONFIG_HOME` is deliberately ignored,
+/// for applications which always want to use `~/.config`.
+pub fn locate_config_strict_home(prefix: &str, filename: &str) -> Option<PathBuf> {
+    validate_names(prefix, filename).ok()?;
+
+    home_dir()
+        .map(|home| home.join(".config").join(prefix).join(filename))
+        .filter(|path| path.exists())
+}
+
+/// Search `filename` in the current directory and each of its ancestors,
+/// up to the filesystem root (e.g. for project-scoped configs)
+pub fn locate_config_ascending(filename: &str) -> Option<PathBuf> {
+    let cwd = std::env::current_dir().ok()?;
+
+    locate_config_ascending_from(cwd, filename, false)
+}
+
+/// Search `filename` in `start` and each of its ancestors.
+///
+/// When `stop_at_git` is set the search ends at the first directory
+/// containing a `.git` entry (i.e. the root of the repository).
+pub fn locate_config_ascending_from(
+    start: impl AsRef<Path>,
+    filename: &str,
+    stop_at_git: bool,
+) -> Option<PathBuf> {
+    for dir in start.as_ref().ancestors() {
+        let path = dir.join(filename);
+
+        if path.is_file() {
+            return Some(path);
+        }
+
+        if stop_at_git && dir.join(".git").exists() {
+            break;
+        }
+    }
+
+    None
+}
+
+/// Try [`locate_config_ascending`] first, falling back to the global
+/// discovery of [`locate_config`]
+pub fn locate_config_nearest(prefix: &str, filename: &str) -> Option<PathBuf> {
+    locate_config_ascending(filename).or_else(|| locate_config(prefix, filename))
+}
+
+/// Find the config named `{base}.{ext}` trying each of `exts` in order
+/// (e.g. `["json", "toml", "yaml"]`) and return it along with its format
+///
+/// Each extension goes through the whole discovery of [`locate_config`]
+/// before trying the next one. Extensions matching no [`Format`] are skipped.
+pub fn locate_config_ext(prefix: &str, base: &str, exts: &[&str]) -> Option<(PathBuf, Format)> {
+    exts.iter().find_map(|ext| {
+        let format = Format::from_extension(ext)?;
+
+        locate_config(prefix, &format!("{base}.{ext}")).map(|path| (path, format))
+    })
+}
+
+/// Same as [`locate_config_ext`] but when several `{base}.{ext}` exist the most
+/// recently modified one is returned, the first of `exts` winning a tie
+pub fn locate_config_newest(prefix: &str, base: &str, exts: &[&str]) -> Option<(PathBuf, Format)> {
+    let mut newest: Option<(PathBuf, Format, Option<SystemTime>)> = None;
+
+    for ext in exts {
+        let Some(format) = Format::from_extension(ext) else {
+            continue;
+        };
+
+        let Some(path) = locate_config(prefix, &format!("{base}.{ext}")) else {
+            continue;
+        };
+
+        let modified = std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok();
+
+        if newest
+            .as_ref()
+            .is_none_or(|(_, _, newest)| modified > *newest)
+        {
+            newest = Some((path, format, modified));
+        }
+    }
+
+    newest.map(|(path, format, _)| (path, format))
+}
+
+/// Load the config from `explicit` when provided (e.g. from a `--config` flag),
+/// falling back to [`locate_config`] otherwise.
+///
+/// Returns the loaded config along with its path and where it has been found.
+pub fn load_explicit_or_discover<T>(
+    explicit: Option<&Path>,
+    prefix: &str,
+    filename: &str,
+) -> crate::Result<(T, PathBuf, ConfigSource)>
+where
+    T: File,
+{
+    let (path, source) = match explicit {
+        Some(path) => (path.to_path_buf(), ConfigSource::Explicit),
+        None => {
+            locate_config_with_source(prefix, filename).ok_or(crate::error::Error::FileNotFound)?
+        }
+    };
+
+    let config = T::load(&path)?;
+
+    Ok((config, path, source))
+}
+
+/// Write `config` to `path` only if the file does not exist yet.
+///
+/// The config is written to a temporary file first, then hard-linked to `path`:
+/// a config created concurrently by another process is never overwritten (in
+/// which case `false` is returned) and a failed write leaves no file behind.
+/// When `private` is set the file is only accessible by its owner (`0600` on Unix).
+fn write_new<T>(config: &T, path: &Path, private: bool) -> crate::Result<bool>
+where
+    T: File,
+{
+    link_new(path, private, |temp| config.write(temp))
+}
+
+/// Same as [`write_new`], the temporary file being filled by `write`
+#[cfg_attr(not(unix), allow(unused_variables))]
+fn link_new(
+    path: &Path,
+    private: bool,
+    write: impl FnOnce(&Path) -> crate::Result<()>,
+) -> crate::Result<bool> {
+    let temp = crate::fs::temp_path(path, "new");
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    options.open(&temp)?;
+
+    let created = write(&temp).and_then(|()| match std::fs::hard_link(&temp, path) {
+        Ok(()) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::AlreadyExists => Ok(false),
+        Err(err) => Err(err.into()),
+    });
+    let _ = std::fs::remove_file(&temp);
+
+    created
 }
 
 /// Initialize the configuration file for the specified type.
@@ -78,15 +567,18 @@ pub fn locate_config(prefix: &str, filename: &str) -> Option<PathBuf> {
 ///
 /// # Arguments
 ///
-/// * `config` - The configuration object to initialize the file with.
+/// * `config` - The configuration object to initialize the file with,
+///   it doesn't have to be `Default` (e.g. when built from runtime values).
 /// * `prefix` - The name of the folder that will contain the configuration file.
 /// * `filename` - The name of the configuration file.
 ///
 ///
 /// # Examples
 ///
-/// ```
-/// use cli_config::fs::JSONFile;
+#[cfg_attr(feature = "json", doc = "```")]
+#[cfg_attr(not(feature = "json"), doc = "```ignore")]
+/// use cli_config::fs::{File, JSONFile};
+/// use std::path::Path;
 ///
 /// #[derive(serde::Serialize, serde::Deserialize, Default)]
 /// struct MyConfig {
@@ -95,6 +587,16 @@ pub fn locate_config(prefix: &str, filename: &str) -> Option<PathBuf> {
 ///
 /// impl JSONFile for MyConfig {}
 ///
+/// impl File for MyConfig {
+///     fn load(path: impl AsRef<Path>) -> cli_config::Result<Self> {
+///         <Self as JSONFile>::load(path)
+///     }
+///
+///     fn write_path(&self, path: &Path) -> cli_config::Result<()> {
+///         JSONFile::write(self, path)
+///     }
+/// }
+///
 /// let config = MyConfig::default();
 /// let prefix = "my-app";
 /// let filename = "config.json";
@@ -103,23 +605,1387 @@ pub fn locate_config(prefix: &str, filename: &str) -> Option<PathBuf> {
 /// let config_path = cli_config::init(config, prefix, filename).unwrap();
 ///
 /// // Use the configuration file
-/// let loaded_config = MyConfig::load(&config_path).unwrap();
+/// let loaded_config = <MyConfig as File>::load(&config_path).unwrap();
 /// println!("Is my first run? {}", loaded_config.is_first_run);
 /// ```
 pub fn init<T>(config: T, prefix: &str, filename: &str) -> crate::Result<PathBuf>
 where
-    T: serde::Serialize + Default + File,
+    T: serde::Serialize + File,
+{
+    init_with(|| config, prefix, filename)
+}
+
+/// Same as [`init`] but an existing config is loaded first so that
+/// an unreadable or corrupt file is reported right away (with its path)
+/// instead of by a later [`File::load`].
+///
+/// The loaded config is discarded, only the path is returned.
+pub fn init_checked<T>(config: T, prefix: &str, filename: &str) -> crate::Result<PathBuf>
+where
+    T: serde::Serialize + File,
+{
+    match locate_config(prefix, filename) {
+        Some(path) => match T::load(&path) {
+            Ok(_) => Ok(path),
+            Err(err) => Err(err.in_file(&path)),
+        },
+        None => init(config, prefix, filename),
+    }
+}
+
+/// Same as [`init`] but a new config is rendered from `template`, replacing its
+/// `{{key}}` placeholders with `substitutions` (e.g. the username or a generated token).
+///
+/// The rendered config must be valid `format`, it's written as-is so comments
+/// of the template are kept. Placeholders without substitution result in
+/// `Error::UnresolvedPlaceholders` listing them.
+///
+/// Substitutions are inserted verbatim: a value must already be escaped for
+/// where its placeholder sits in `format` (e.g. `\"` inside a JSON string),
+/// otherwise the rendered config fails to parse and nothing is written.
+/// Like [`init`], a failed write leaves no config behind.
+pub fn init_from_template(
+    template: &str,
+    format: Format,
+    substitutions: &std::collections::HashMap<String, String>,
+    prefix: &str,
+    filename: &str,
+) -> crate::Result<PathBuf> {
+    if let Some(path) = locate_config(prefix, filename) {
+        return Ok(path);
+    }
+
+    let content = render_template(template, substitutions)?;
+    format.parse::<serde_json::Value>(&content)?;
+
+    let path = get_new_config_path(prefix, filename)?
+        .ok_or(crate::error::Error::Custom("Could not create file"))?;
+
+    let written = link_new(&path, false, |temp| Ok(std::fs::write(temp, &content)?))?;
+    if !written {
+        // created concurrently
+        return Ok(locate_config(prefix, filename).unwrap_or(path));
+    }
+
+    Ok(path)
+}
+
+/// Replace the `{{key}}` placeholders of `template`, see [`init_from_template`]
+fn render_template(
+    template: &str,
+    substitutions: &std::collections::HashMap<String, String>,
+) -> crate::Result<String> {
+    let mut rendered = String::with_capacity(template.len());
+    let mut unresolved = Vec::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+
+        let key = rest[start + 2..start + end].trim();
+        rendered.push_str(&rest[..start]);
+
+        match substitutions.get(key) {
+            Some(value) => rendered.push_str(value),
+            None if !unresolved.iter().any(|unresolved| unresolved == key) => {
+                unresolved.push(key.to_string())
+            }
+            None => {}
+        }
+
+        rest = &rest[start + end + 2..];
+    }
+
+    rendered.push_str(rest);
+
+    if !unresolved.is_empty() {
+        return Err(crate::error::Error::UnresolvedPlaceholders(unresolved));
+    }
+
+    Ok(rendered)
+}
+
+/// Same as [`init`] but the config is only built, by calling `make_default`,
+/// when a new file has to be written
+pub fn init_with<T, F>(make_default: F, prefix: &str, filename: &str) -> crate::Result<PathBuf>
+where
+    T: serde::Serialize + File,
+    F: FnOnce() -> T,
 {
     let config_path = locate_config(prefix, filename);
 
     match config_path {
-        None => match get_new_config_path(prefix, filename) {
+        None => match get_new_config_path(prefix, filename)? {
             None => Err(crate::error::Error::Custom("Could not create file")),
-            Some(path) => {
-                config.write(&path)?;
-                Ok(path)
-            }
+            Some(path) => create_config(&make_default(), prefix, filename, path),
         },
         Some(path) => Ok(path),
     }
 }
+
+/// Same as [`init`] but returns `Ok(None)` instead of failing when no config
+/// location can be determined, e.g. in a sandbox without `$HOME` nor `$XDG_CONFIG_HOME`.
+///
+/// `Err` is reserved for actual failures (e.g. IO errors while creating the file).
+pub fn try_init<T>(config: T, prefix: &str, filename: &str) -> crate::Result<Option<PathBuf>>
+where
+    T: serde::Serialize + File,
+{
+    if let Some(path) = locate_config(prefix, filename) {
+        return Ok(Some(path));
+    }
+
+    // don't fall back to the home directory of the user database
+    #[cfg(not(windows))]
+    if ["HOME", "XDG_CONFIG_HOME"]
+        .into_iter()
+        .all(|key| std::env::var_os(key).is_none_or(|value| value.is_empty()))
+    {
+        return Ok(None);
+    }
+
+    match get_new_config_path(prefix, filename)? {
+        None => Ok(None),
+        Some(path) => create_config(&config, prefix, filename, path).map(Some),
+    }
+}
+
+/// Same as [`init`] but for configs holding secrets: a newly created prefix
+/// directory is only accessible by its owner (`0700` on Unix) and so is
+/// the config file itself (`0600`).
+///
+/// Existing directories and files are left untouched.
+pub fn init_private<T>(config: T, prefix: &str, filename: &str) -> crate::Result<PathBuf>
+where
+    T: serde::Serialize + File,
+{
+    if let Some(path) = locate_config(prefix, filename) {
+        return Ok(path);
+    }
+
+    validate_names(prefix, filename)?;
+    check_config_dir(prefix)?;
+
+    let dir = get_config_dir(prefix).ok_or(crate::error::Error::Custom("Could not create file"))?;
+    create_private_dir(&dir)?;
+
+    let path = dir.join(filename);
+    if !write_new(&config, &path, true)? {
+        return Ok(locate_config(prefix, filename).unwrap_or(path));
+    }
+
+    Ok(path)
+}
+
+/// Create `dir` (and its missing parents) only accessible by the owner
+fn create_private_dir(dir: &Path) -> io::Result<()> {
+    let mut builder = std::fs::DirBuilder::new();
+    builder.recursive(true);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+
+    builder.create(dir)
+}
+
+/// Create the config directory of `prefix` (`$XDG_CONFIG_HOME/{prefix}`,
+/// see [`get_config_dir`]) without writing any config file and return it,
+/// e.g. to put a lockfile there before the config is initialized
+///
+/// Nothing happens when the directory already exists.
+pub fn create_config_dir(prefix: &str) -> crate::Result<PathBuf> {
+    if !is_plain_name(prefix) {
+        return Err(crate::error::Error::InvalidConfig("invalid prefix"));
+    }
+
+    check_config_dir(prefix)?;
+
+    let dir =
+        get_config_dir(prefix).ok_or(crate::error::Error::Custom("Could not create directory"))?;
+
+    #[cfg(windows)]
+    let dir = extended_length(&dir);
+
+    std::fs::create_dir_all(&dir)?;
+
+    Ok(dir)
+}
+
+/// Create the config at `path` unless it has been created concurrently,
+/// in which case the existing file is used instead of overwriting it.
+fn create_config<T>(
+    config: &T,
+    prefix: &str,
+    filename: &str,
+    path: PathBuf,
+) -> crate::Result<PathBuf>
+where
+    T: File,
+{
+    if !write_new(config, &path, false)? {
+        return Ok(locate_config(prefix, filename).unwrap_or(path));
+    }
+
+    Ok(path)
+}
+
+/// Overwrite the config file with `T::default()`, creating it if needed.
+///
+/// Unlike [`init`] an existing config is **replaced**. When `backup` is set
+/// the previous file is first copied to [`crate::fs::backup_path`].
+pub fn reset_to_default<T>(prefix: &str, filename: &str, backup: bool) -> crate::Result<PathBuf>
+where
+    T: serde::Serialize + Default + File,
+{
+    let path = match locate_config(prefix, filename) {
+        Some(path) => path,
+        None => get_new_config_path(prefix, filename)?
+            .ok_or(crate::error::Error::Custom("Could not create file"))?,
+    };
+
+    if backup && path.exists() {
+        std::fs::copy(&path, crate::fs::backup_path(&path))?;
+    }
+
+    T::default().write(&path)?;
+
+    Ok(path)
+}
+
+/// Open the config located by [`locate_config`] (created with `T::default()`
+/// when missing, see [`init`]) in `$VISUAL` or `$EDITOR` and wait for the
+/// editor to exit, e.g. for a `config edit` command
+///
+/// The edited file is then loaded: when it's no longer a valid `T` its previous
+/// content is restored and the load error returned, so that the caller can
+/// report it (and e.g. offer to edit again). The previous content is restored
+/// as well when the editor exits with an error.
+///
+/// Fails with `Error::Custom` when neither `$VISUAL` nor `$EDITOR` is set.
+pub fn edit_config<T>(prefix: &str, filename: &str) -> crate::Result<()>
+where
+    T: serde::Serialize + Default + File,
+{
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .ok()
+        .filter(|editor| !editor.trim().is_empty())
+        .ok_or(crate::error::Error::Custom(
+            "no editor configured, set $VISUAL or $EDITOR",
+        ))?;
+
+    let path = init(T::default(), prefix, filename)?;
+    let previous = std::fs::read(&path)?;
+
+    // the editor can come with arguments, e.g. `code --wait`
+    let mut args = editor.split_whitespace();
+    let program = args.next().unwrap_or_default();
+
+    let status = std::process::Command::new(program)
+        .args(args)
+        .arg(&path)
+        .status()?;
+
+    if !status.success() {
+        std::fs::write(&path, &previous)?;
+        return Err(crate::error::Error::Custom(
+            "the editor exited with an error",
+        ));
+    }
+
+    if let Err(err) = T::load(&path) {
+        std::fs::write(&path, &previous)?;
+        return Err(err.in_file(&path));
+    }
+
+    Ok(())
+}
+
+/// Move the config of `old_prefix` to the location of `new_prefix`,
+/// e.g. after renaming the application.
+///
+/// Nothing is done (and `None` returned) when there is no old config
+/// or when a config already exists for `new_prefix`.
+/// Otherwise the old file is moved (copied then removed across file systems)
+/// and its new path returned.
+pub fn migrate_location(
+    old_prefix: &str,
+    new_prefix: &str,
+    filename: &str,
+) -> crate::Result<Option<PathBuf>> {
+    let Some(old_path) = locate_config(old_prefix, filename) else {
+        return Ok(None);
+    };
+
+    if locate_config(new_prefix, filename).is_some() {
+        return Ok(None);
+    }
+
+    let new_path = get_new_config_path(new_prefix, filename)?
+        .ok_or(crate::error::Error::Custom("Could not create file"))?;
+
+    if std::fs::rename(&old_path, &new_path).is_err() {
+        std::fs::copy(&old_path, &new_path)?;
+        std::fs::remove_file(&old_path)?;
+    }
+
+    Ok(Some(new_path))
+}
+
+/// Try to find the config file inside the platform-specific config directory
+/// resolved by [`directories::ProjectDirs`]
+///
+/// - `qualifier` is the reverse domain name notation of the application (e.g. `com`)
+/// - `organization` is the name of the organization that develops the application
+/// - `application` is the name of the application itself
+#[cfg(feature = "directories")]
+pub fn locate_config_project(
+    qualifier: &str,
+    organization: &str,
+    application: &str,
+    filename: &str,
+) -> Option<PathBuf> {
+    directories::ProjectDirs::from(qualifier, organization, application)
+        .map(|dirs| dirs.config_dir().join(filename))
+        .filter(|p| p.exists())
+}
+
+/// Same as [`init`] but resolves the config location using [`directories::ProjectDirs`]
+/// which follows the conventions of each platform (XDG, Known Folder, Standard Directories).
+///
+/// See [`locate_config_project`] for the meaning of `qualifier`, `organization` and `application`.
+#[cfg(feature = "directories")]
+pub fn init_project<T>(
+    config: T,
+    qualifier: &str,
+    organization: &str,
+    application: &str,
+    filename: &str,
+) -> crate::Result<PathBuf>
+where
+    T: serde::Serialize + File,
+{
+    if let Some(path) = locate_config_project(qualifier, organization, application, filename) {
+        return Ok(path);
+    }
+
+    let dirs = directories::ProjectDirs::from(qualifier, organization, application)
+        .ok_or(crate::error::Error::Custom("Could not create file"))?;
+
+    std::fs::create_dir_all(dirs.config_dir())?;
+
+    let path = dirs.config_dir().join(filename);
+    write_new(&config, &path, false)?;
+
+    Ok(path)
+}
+
+/// Metadata about a located config file
+#[derive(Debug, Clone)]
+pub struct ConfigInfo {
+    /// Location of the config file
+    pub path: PathBuf,
+    /// Size in bytes
+    pub size: u64,
+    /// Last modification time
+    pub modified: SystemTime,
+    /// Format detected from the file extension, if any
+    pub format: Option<Format>,
+}
+
+/// Get the metadata of the config file located by [`locate_config`]
+///
+/// Returns `Error::FileNotFound` if no config file exists.
+pub fn config_metadata(prefix: &str, filename: &str) -> crate::Result<ConfigInfo> {
+    let Some(path) = locate_config(prefix, filename) else {
+        check_config_dir(prefix)?;
+        return Err(crate::error::Error::FileNotFound);
+    };
+    let metadata = std::fs::metadata(&path)?;
+
+    Ok(ConfigInfo {
+        size: metadata.len(),
+        modified: metadata.modified()?,
+        format: Format::from_path(&path),
+        path,
+    })
+}
+
+/// Time elapsed since the config file located by [`locate_config`] was last modified
+///
+/// A modification time in the future counts as zero.
+/// Returns `Error::FileNotFound` if no config file exists.
+pub fn config_age(prefix: &str, filename: &str) -> crate::Result<std::time::Duration> {
+    let modified = config_metadata(prefix, filename)?.modified;
+
+    Ok(SystemTime::now()
+        .duration_since(modified)
+        .unwrap_or_default())
+}
+
+/// Whether the config file is older than `max_age` (see [`config_age`]),
+/// e.g. to refresh a config cached from a server
+///
+/// A missing config file is stale.
+pub fn is_stale(prefix: &str, filename: &str, max_age: std::time::Duration) -> crate::Result<bool> {
+    match config_age(prefix, filename) {
+        Ok(age) => Ok(age > max_age),
+        Err(crate::error::Error::FileNotFound) => Ok(true),
+        Err(err) => Err(err),
+    }
+}
+
+/// Read the raw content of the config file located by [`locate_config`],
+/// without deserializing it (e.g. to display or hash it)
+///
+/// Returns `Error::FileNotFound` if no config file exists.
+pub fn read_config_bytes(prefix: &str, filename: &str) -> crate::Result<Vec<u8>> {
+    let Some(path) = locate_config(prefix, filename) else {
+        check_config_dir(prefix)?;
+        return Err(crate::error::Error::FileNotFound);
+    };
+
+    Ok(std::fs::read(path)?)
+}
+
+/// Same as [`read_config_bytes`] but the content must be valid UTF-8
+pub fn read_config_string(prefix: &str, filename: &str) -> crate::Result<String> {
+    let content = read_config_bytes(prefix, filename)?;
+
+    String::from_utf8(content)
+        .map_err(|_| crate::error::Error::InvalidConfig("config is not valid UTF-8"))
+}
+
+/// Write `content` as-is into the config file located by [`locate_config`],
+/// creating it (see [`init`]) when none exists, and return its path
+pub fn write_config_bytes(
+    prefix: &str,
+    filename: &str,
+    content: impl AsRef<[u8]>,
+) -> crate::Result<PathBuf> {
+    let path = match locate_config(prefix, filename) {
+        Some(path) => path,
+        None => get_new_config_path(prefix, filename)?
+            .ok_or(crate::error::Error::Custom("Could not create file"))?,
+    };
+
+    std::fs::write(&path, content)?;
+
+    Ok(path)
+}
+
+/// The raw content of a config file captured by [`snapshot`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigSnapshot {
+    path: PathBuf,
+    content: Vec<u8>,
+}
+
+impl ConfigSnapshot {
+    /// Path of the captured config file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Rewrite the config file with the captured content,
+    /// recreating it if it has been removed since
+    pub fn restore(&self) -> crate::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        Ok(std::fs::write(&self.path, &self.content)?)
+    }
+}
+
+/// Capture the raw content of the config file located by [`locate_config`]
+/// so that it can be restored later, e.g. around a risky migration or a test
+///
+/// Unlike a backup nothing is written, the content stays in memory.
+/// Returns `Error::FileNotFound` if no config file exists.
+pub fn snapshot(prefix: &str, filename: &str) -> crate::Result<ConfigSnapshot> {
+    let Some(path) = locate_config(prefix, filename) else {
+        check_config_dir(prefix)?;
+        return Err(crate::error::Error::FileNotFound);
+    };
+
+    Ok(ConfigSnapshot {
+        content: std::fs::read(&path)?,
+        path,
+    })
+}
+
+#[cfg(all(test, not(windows)))]
+mod raw_tests {
+    use super::*;
+    use crate::error::Error;
+    use crate::test_utils::TempHome;
+
+    #[test]
+    fn test_raw_round_trip() {
+        let home = TempHome::new();
+        let content = b"\x00\xffnot a config\n";
+
+        let path = write_config_bytes("my-app", "config.bin", content).unwrap();
+        assert_eq!(path, home.config_dir().join("my-app/config.bin"));
+        assert_eq!(read_config_bytes("my-app", "config.bin").unwrap(), content);
+
+        // the existing file is overwritten
+        write_config_bytes("my-app", "config.bin", "foo = \"bar\"").unwrap();
+        assert_eq!(
+            read_config_string("my-app", "config.bin").unwrap(),
+            "foo = \"bar\""
+        );
+    }
+
+    #[test]
+    fn test_create_config_dir() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+
+        assert_eq!(create_config_dir("my-app").unwrap(), dir);
+        assert!(dir.is_dir());
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 0);
+
+        // a second call keeps the existing content
+        std::fs::write(dir.join("app.lock"), "").unwrap();
+        assert_eq!(create_config_dir("my-app").unwrap(), dir);
+        assert!(dir.join("app.lock").exists());
+
+        assert!(matches!(
+            create_config_dir("../my-app"),
+            Err(Error::InvalidConfig("invalid prefix"))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_restore() {
+        let _home = TempHome::new();
+        let path = write_config_bytes("my-app", "config.toml", "foo = 1\n").unwrap();
+
+        let snapshot = snapshot("my-app", "config.toml").unwrap();
+        assert_eq!(snapshot.path(), path);
+
+        std::fs::write(&path, "foo = 2\n").unwrap();
+        snapshot.restore().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "foo = 1\n");
+
+        std::fs::remove_file(&path).unwrap();
+        snapshot.restore().unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "foo = 1\n");
+
+        assert!(matches!(
+            super::snapshot("my-app", "missing.toml"),
+            Err(Error::FileNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_config_age() {
+        let _home = TempHome::new();
+        let hour = std::time::Duration::from_secs(3600);
+
+        assert!(matches!(
+            config_age("my-app", "config.bin"),
+            Err(Error::FileNotFound)
+        ));
+        assert!(is_stale("my-app", "config.bin", hour).unwrap());
+
+        write_config_bytes("my-app", "config.bin", "cached").unwrap();
+
+        assert!(config_age("my-app", "config.bin").unwrap() < std::time::Duration::from_secs(5));
+        assert!(!is_stale("my-app", "config.bin", hour).unwrap());
+
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        assert!(is_stale("my-app", "config.bin", std::time::Duration::from_millis(1)).unwrap());
+    }
+
+    #[test]
+    fn test_platform_filename() {
+        let home = TempHome::new();
+        let filename = PlatformFilename {
+            windows: "settings.ini",
+            unix: ".myapprc",
+        };
+
+        assert_eq!(filename.current(), ".myapprc");
+
+        let path = write_config_bytes("my-app", &filename, "verbose = true").unwrap();
+        assert_eq!(path, home.config_dir().join("my-app/.myapprc"));
+        assert_eq!(locate_config("my-app", &filename), Some(path));
+    }
+
+    #[test]
+    fn test_raw_errors() {
+        let _home = TempHome::new();
+
+        assert!(matches!(
+            read_config_bytes("my-app", "config.bin"),
+            Err(Error::FileNotFound)
+        ));
+
+        write_config_bytes("my-app", "config.bin", [0xff, 0xfe]).unwrap();
+        assert!(matches!(
+            read_config_string("my-app", "config.bin"),
+            Err(Error::InvalidConfig(_))
+        ));
+    }
+}
+
+#[cfg(all(test, windows))]
+mod windows_tests {
+    use super::*;
+
+    fn long_tail() -> PathBuf {
+        (0..30).map(|i| format!("directory-{i}")).collect()
+    }
+
+    #[test]
+    fn test_platform_filename() {
+        let filename = PlatformFilename {
+            windows: "settings.ini",
+            unix: ".myapprc",
+        };
+
+        assert_eq!(filename.current(), "settings.ini");
+        assert_eq!(&*filename, "settings.ini");
+    }
+
+    #[test]
+    fn test_extended_length() {
+        let short = Path::new(r"C:\Users\me\AppData\Roaming\my-app\config.json");
+        assert_eq!(extended_length(short), short);
+
+        let disk = Path::new(r"C:\Users").join(long_tail());
+        let extended = extended_length(&disk);
+        assert!(extended.starts_with(r"\\?\C:\Users\directory-0"));
+        assert!(extended.ends_with("directory-29"));
+
+        let unc = Path::new(r"\\server\share").join(long_tail());
+        let extended = extended_length(&unc);
+        assert!(extended
+            .to_string_lossy()
+            .starts_with(r"\\?\UNC\server\share\directory-0\"));
+        assert!(extended.ends_with("directory-29"));
+
+        // already extended
+        assert_eq!(extended_length(&extended), extended);
+    }
+
+    #[test]
+    fn test_long_path_io() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = extended_length(&dir.path().join(long_tail()));
+
+        std::fs::create_dir_all(&path).unwrap();
+        std::fs::write(path.join("config.json"), "{}").unwrap();
+
+        assert!(path.join("config.json").exists());
+    }
+}
+
+#[cfg(all(test, feature = "json"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TempHome, TestConfig};
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_config_metadata() {
+        let _home = TempHome::new();
+
+        assert!(matches!(
+            config_metadata("my-app", "config.json"),
+            Err(crate::error::Error::FileNotFound)
+        ));
+
+        let path = init(TestConfig::default(), "my-app", "config.json").unwrap();
+        let info = config_metadata("my-app", "config.json").unwrap();
+
+        assert_eq!(info.path, path);
+        assert_eq!(info.size, std::fs::metadata(&path).unwrap().len());
+        assert!(info.size > 0);
+        assert_eq!(info.format, Some(Format::Json));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_load_explicit_or_discover() {
+        let home = TempHome::new();
+        let discovered = init(TestConfig::default(), "my-app", "config.json").unwrap();
+
+        let (_, path, source) =
+            load_explicit_or_discover::<TestConfig>(None, "my-app", "config.json").unwrap();
+        assert_eq!(path, discovered);
+        assert_eq!(source, ConfigSource::XdgConfig);
+
+        let explicit = home.dir.path().join("explicit.json");
+        let config = TestConfig {
+            foo: "explicit".to_string(),
+            ..TestConfig::default()
+        };
+        config.write(&explicit).unwrap();
+
+        let (loaded, path, source) =
+            load_explicit_or_discover::<TestConfig>(Some(&explicit), "my-app", "config.json")
+                .unwrap();
+        assert_eq!(loaded, config);
+        assert_eq!(path, explicit);
+        assert_eq!(source, ConfigSource::Explicit);
+        assert_eq!(source.to_string(), "command-line --config flag");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_locate_config_strict_home() {
+        let home = TempHome::new();
+        let xdg = home.dir.path().join("elsewhere");
+        std::env::set_var("XDG_CONFIG_HOME", &xdg);
+
+        std::fs::create_dir_all(xdg.join("my-app")).unwrap();
+        TestConfig::default()
+            .write(xdg.join("my-app/config.json"))
+            .unwrap();
+
+        assert_eq!(locate_config_strict_home("my-app", "config.json"), None);
+
+        let strict = home.dir.path().join(".config/my-app/config.json");
+        std::fs::create_dir_all(strict.parent().unwrap()).unwrap();
+        TestConfig::default().write(&strict).unwrap();
+
+        assert_eq!(
+            locate_config_strict_home("my-app", "config.json"),
+            Some(strict)
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_reset_to_default() {
+        let _home = TempHome::new();
+        let path = init(TestConfig::default(), "my-app", "config.json").unwrap();
+
+        let modified = TestConfig {
+            foo: "modified".to_string(),
+            ..TestConfig::default()
+        };
+        modified.write(&path).unwrap();
+
+        let reset = reset_to_default::<TestConfig>("my-app", "config.json", true).unwrap();
+
+        assert_eq!(reset, path);
+        assert_eq!(TestConfig::load(&path).unwrap(), TestConfig::default());
+        assert_eq!(
+            TestConfig::load(crate::fs::backup_path(&path)).unwrap(),
+            modified
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_init_without_default() {
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct ServerConfig {
+            url: String,
+        }
+
+        impl crate::fs::JSONFile for ServerConfig {}
+
+        impl File for ServerConfig {
+            fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+                <Self as crate::fs::JSONFile>::load(path)
+            }
+
+            fn write_path(&self, path: &Path) -> crate::Result<()> {
+                crate::fs::JSONFile::write(self, path)
+            }
+        }
+
+        let _home = TempHome::new();
+        let config = ServerConfig {
+            url: "https://home.example".to_string(),
+        };
+
+        let path = init(config, "my-app", "config.json").unwrap();
+
+        assert_eq!(
+            ServerConfig::load(&path).unwrap().url,
+            "https://home.example"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_init_from_template() {
+        let _home = TempHome::new();
+        let template = r#"{ "foo": "{{ user }}", "bar": true, "baz": {{port}} }"#;
+        let substitutions = std::collections::HashMap::from([
+            ("user".to_string(), "alice".to_string()),
+            ("port".to_string(), "8080".to_string()),
+        ]);
+
+        let path = init_from_template(
+            template,
+            Format::Json,
+            &substitutions,
+            "my-app",
+            "config.json",
+        )
+        .unwrap();
+
+        let config = TestConfig::load(&path).unwrap();
+        assert_eq!(config.foo, "alice");
+        assert_eq!(config.baz, 8080);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_init_from_template_unescaped() {
+        let home = TempHome::new();
+        let template = r#"{ "foo": "{{user}}", "bar": true, "baz": 1 }"#;
+        let init = |user: &str| {
+            let substitutions =
+                std::collections::HashMap::from([("user".to_string(), user.to_string())]);
+            init_from_template(
+                template,
+                Format::Json,
+                &substitutions,
+                "my-app",
+                "config.json",
+            )
+        };
+
+        assert_eq!(init(r#"al"ice"#).unwrap_err().kind(), "json");
+        assert!(!home.config_dir().join("my-app/config.json").exists());
+
+        let path = init(r#"al\"ice"#).unwrap();
+        assert_eq!(TestConfig::load(&path).unwrap().foo, r#"al"ice"#);
+        // only the config is left
+        assert_eq!(
+            std::fs::read_dir(path.parent().unwrap()).unwrap().count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_render_template() {
+        let substitutions =
+            std::collections::HashMap::from([("user".to_string(), "alice".to_string())]);
+
+        assert_eq!(
+            render_template("name = \"{{user}}\" # {{ user }}", &substitutions).unwrap(),
+            "name = \"alice\" # alice"
+        );
+        assert_eq!(
+            render_template("no placeholder {{ unterminated", &substitutions).unwrap(),
+            "no placeholder {{ unterminated"
+        );
+
+        let err =
+            render_template("{{token}} {{user}} {{host}} {{token}}", &substitutions).unwrap_err();
+        assert!(matches!(
+            &err,
+            crate::error::Error::UnresolvedPlaceholders(keys) if keys == &["token", "host"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "unresolved template placeholders: token, host"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_init_checked() {
+        let home = TempHome::new();
+
+        let path = init_checked(TestConfig::default(), "my-app", "config.json").unwrap();
+        assert_eq!(path, home.config_dir().join("my-app/config.json"));
+        assert_eq!(
+            init_checked(TestConfig::default(), "my-app", "config.json").unwrap(),
+            path
+        );
+
+        std::fs::write(&path, r#"{ "foo": "#).unwrap();
+
+        // `init` doesn't look at the content
+        assert_eq!(
+            init(TestConfig::default(), "my-app", "config.json").unwrap(),
+            path
+        );
+
+        let err = init_checked(TestConfig::default(), "my-app", "config.json").unwrap_err();
+        assert!(
+            matches!(&err, crate::error::Error::InFile { path: err_path, .. } if err_path == &path)
+        );
+        assert_eq!(err.kind(), "json");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_locate_config_ext() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.toml"), "foo = 1").unwrap();
+
+        assert_eq!(
+            locate_config_ext("my-app", "config", &["json", "toml", "yaml"]),
+            Some((dir.join("config.toml"), Format::Toml))
+        );
+        assert_eq!(
+            locate_config_ext("my-app", "config", &["json", "yaml"]),
+            None
+        );
+        assert_eq!(locate_config_ext("my-app", "config", &["ini"]), None);
+
+        // the first extension found wins
+        std::fs::write(dir.join("config.json"), "{}").unwrap();
+        assert_eq!(
+            locate_config_ext("my-app", "config", &["toml", "json"]),
+            Some((dir.join("config.toml"), Format::Toml))
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_locate_config_newest() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let touch = |name: &str, secs: u64| {
+            let file = std::fs::File::create(dir.join(name)).unwrap();
+            file.set_modified(SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(secs))
+                .unwrap();
+        };
+
+        touch("config.json", 1_000);
+        touch("config.toml", 2_000);
+
+        assert_eq!(
+            locate_config_newest("my-app", "config", &["json", "toml"]),
+            Some((dir.join("config.toml"), Format::Toml))
+        );
+
+        touch("config.json", 3_000);
+        assert_eq!(
+            locate_config_newest("my-app", "config", &["toml", "json"]),
+            Some((dir.join("config.json"), Format::Json))
+        );
+
+        // ties are broken by the order of the extensions
+        touch("config.toml", 3_000);
+        assert_eq!(
+            locate_config_newest("my-app", "config", &["toml", "json"]),
+            Some((dir.join("config.toml"), Format::Toml))
+        );
+        assert_eq!(locate_config_newest("my-app", "config", &["yaml"]), None);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_migrate_location() {
+        let home = TempHome::new();
+        let old = init(TestConfig::default(), "old-app", "config.json").unwrap();
+
+        let migrated = migrate_location("old-app", "new-app", "config.json").unwrap();
+
+        assert_eq!(
+            migrated,
+            Some(home.config_dir().join("new-app/config.json"))
+        );
+        assert!(!old.exists());
+        assert_eq!(
+            TestConfig::load(migrated.unwrap()).unwrap(),
+            TestConfig::default()
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_migrate_location_both_exist() {
+        let _home = TempHome::new();
+        let old = init(TestConfig::default(), "old-app", "config.json").unwrap();
+        let new = init(
+            TestConfig {
+                foo: "new".to_string(),
+                ..TestConfig::default()
+            },
+            "new-app",
+            "config.json",
+        )
+        .unwrap();
+
+        assert_eq!(
+            migrate_location("old-app", "new-app", "config.json").unwrap(),
+            None
+        );
+        assert!(old.exists());
+        assert_eq!(TestConfig::load(&new).unwrap().foo, "new");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_migrate_location_neither_exist() {
+        let home = TempHome::new();
+
+        assert_eq!(
+            migrate_location("old-app", "new-app", "config.json").unwrap(),
+            None
+        );
+        assert!(!home.config_dir().join("new-app").exists());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_locate_config_strict() {
+        let home = TempHome::new();
+        let path = init(TestConfig::default(), "my-app", "config.json").unwrap();
+
+        assert_eq!(
+            locate_config_strict("my-app", "config.json").unwrap(),
+            Some(path)
+        );
+        assert_eq!(locate_config_strict("my-app", "other.json").unwrap(), None);
+
+        std::env::set_var("XDG_CONFIG_HOME", "relative/config");
+        reset_config_cache();
+
+        assert!(matches!(
+            locate_config_strict("my-app", "config.json"),
+            Err(crate::error::Error::InvalidXdgConfigHome(dir)) if dir == Path::new("relative/config")
+        ));
+        // the lenient version falls back to `$HOME/.config`
+        assert_eq!(
+            locate_config("my-app", "config.json"),
+            Some(home.config_dir().join("my-app/config.json"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_edit_config() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = TempHome::new();
+        let path = home.config_dir().join("my-app/config.json");
+
+        // writes its first argument into the edited file
+        let editor = home.dir.path().join("editor.sh");
+        std::fs::write(&editor, "#!/bin/sh\nprintf '%s' \"$1\" > \"$2\"\n").unwrap();
+        std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let previous = ["VISUAL", "EDITOR"].map(|key| (key, std::env::var_os(key)));
+        std::env::remove_var("VISUAL");
+
+        std::env::remove_var("EDITOR");
+        assert!(matches!(
+            edit_config::<TestConfig>("my-app", "config.json"),
+            Err(crate::error::Error::Custom(_))
+        ));
+
+        let valid = r#"{"foo":"edited","bar":false,"baz":7}"#;
+        std::env::set_var("EDITOR", format!("{} {valid}", editor.display()));
+        edit_config::<TestConfig>("my-app", "config.json").unwrap();
+        assert_eq!(TestConfig::load(&path).unwrap().foo, "edited");
+
+        // an invalid edit is rolled back
+        std::env::set_var("EDITOR", format!("{} {{", editor.display()));
+        assert!(edit_config::<TestConfig>("my-app", "config.json").is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), valid);
+
+        std::env::set_var("EDITOR", "false");
+        assert!(matches!(
+            edit_config::<TestConfig>("my-app", "config.json"),
+            Err(crate::error::Error::Custom(_))
+        ));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), valid);
+
+        for (key, value) in previous {
+            match value {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
+            }
+        }
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_try_init() {
+        let home = TempHome::new();
+
+        let path = try_init(TestConfig::default(), "my-app", "config.json").unwrap();
+        assert_eq!(path, Some(home.config_dir().join("my-app/config.json")));
+
+        std::env::remove_var("HOME");
+        std::env::remove_var("XDG_CONFIG_HOME");
+
+        let path = try_init(TestConfig::default(), "other-app", "config.json").unwrap();
+        assert_eq!(path, None);
+    }
+
+    #[test]
+    fn test_locate_config_ascending() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let start = dir.path().join("project/src/module");
+        std::fs::create_dir_all(&start).unwrap();
+
+        let config = dir.path().join("project/.myapp.json");
+        TestConfig::default().write(&config).unwrap();
+
+        assert_eq!(
+            locate_config_ascending_from(&start, ".myapp.json", false),
+            Some(config.clone())
+        );
+
+        // the repository root is below the config
+        std::fs::create_dir(dir.path().join("project/src/.git")).unwrap();
+        assert_eq!(
+            locate_config_ascending_from(&start, ".myapp.json", true),
+            None
+        );
+        assert_eq!(
+            locate_config_ascending_from(&start, ".myapp.json", false),
+            Some(config)
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_init_private() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = TempHome::new();
+        let path = init_private(TestConfig::default(), "my-app", "config.json").unwrap();
+
+        assert_eq!(path, home.config_dir().join("my-app/config.json"));
+
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(path.parent().unwrap()), 0o700);
+        assert_eq!(mode(&path), 0o600);
+        assert_eq!(TestConfig::load(&path).unwrap(), TestConfig::default());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_config_dir() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o000)).unwrap();
+
+        // privileged users (e.g. root) bypass the permissions
+        if std::fs::read_dir(&dir).is_err() {
+            let err = init(TestConfig::default(), "my-app", "config.json").unwrap_err();
+            assert!(
+                matches!(&err, crate::error::Error::PermissionDenied(path) if path == &dir),
+                "{err}"
+            );
+            assert!(err.to_string().contains("my-app"));
+        }
+
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_path_traversal() {
+        let home = TempHome::new();
+        let invalid = |err| matches!(err, crate::error::Error::InvalidConfig(_));
+
+        // `$HOME/.config/../outside/config.json`
+        std::fs::create_dir_all(home.dir.path().join("outside")).unwrap();
+        std::fs::write(home.dir.path().join("outside/config.json"), "{}").unwrap();
+        assert_eq!(locate_config_strict_home("../outside", "config.json"), None);
+
+        for prefix in ["../../etc", "..", "/etc", "my-app/../..", "..\\windows"] {
+            assert_eq!(locate_config(prefix, "config.json"), None);
+            assert_eq!(get_config_dir(prefix), None);
+            assert!(invalid(
+                init(TestConfig::default(), prefix, "config.json").unwrap_err()
+            ));
+        }
+
+        for filename in ["../config.json", "/etc/passwd", "C:\\config.json", ""] {
+            assert_eq!(locate_config("my-app", filename), None);
+            assert!(invalid(
+                init(TestConfig::default(), "my-app", filename).unwrap_err()
+            ));
+        }
+
+        assert!(matches!(
+            init(TestConfig::default(), "../etc", "config.json"),
+            Err(crate::error::Error::InvalidConfig("invalid prefix"))
+        ));
+        assert!(init(TestConfig::default(), "my-app", "config.json").is_ok());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_init_with() {
+        let _home = TempHome::new();
+        let mut calls = 0;
+
+        let path = init_with(
+            || {
+                calls += 1;
+                TestConfig::default()
+            },
+            "my-app",
+            "config.json",
+        )
+        .unwrap();
+        assert_eq!(calls, 1);
+
+        let existing = init_with::<TestConfig, _>(|| unreachable!(), "my-app", "config.json");
+        assert_eq!(existing.unwrap(), path);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_base_directories_cache() {
+        use std::sync::atomic::Ordering;
+
+        let home = TempHome::new();
+        let path = init(TestConfig::default(), "my-app", "config.json").unwrap();
+
+        let computed = BASE_DIRECTORIES_COMPUTED.load(Ordering::SeqCst);
+        for _ in 0..10 {
+            assert_eq!(locate_config("my-app", "config.json"), Some(path.clone()));
+        }
+        assert_eq!(BASE_DIRECTORIES_COMPUTED.load(Ordering::SeqCst), computed);
+
+        // environment changes are only seen after a reset
+        std::env::set_var("XDG_CONFIG_HOME", home.dir.path().join("elsewhere"));
+        assert_eq!(locate_config("my-app", "config.json"), Some(path.clone()));
+
+        reset_config_cache();
+        assert_eq!(
+            locate_config_with_source("my-app", "config.json"),
+            Some((path, ConfigSource::HomeConfig))
+        );
+        assert!(BASE_DIRECTORIES_COMPUTED.load(Ordering::SeqCst) > computed);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_locate_yaml_spellings() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("config.yml"), "foo: yml").unwrap();
+
+        assert_eq!(
+            locate_config("my-app", "config.yml"),
+            Some(dir.join("config.yml"))
+        );
+        assert_eq!(
+            locate_config("my-app", "config.yaml"),
+            Some(dir.join("config.yml"))
+        );
+        assert_eq!(locate_config("my-app", "config.json"), None);
+
+        // the exact spelling wins
+        std::fs::write(dir.join("config.yaml"), "foo: yaml").unwrap();
+        assert_eq!(
+            locate_config("my-app", "config.yaml"),
+            Some(dir.join("config.yaml"))
+        );
+
+        assert_eq!(filename_variants("config.JSON"), vec!["config.JSON"]);
+    }
+
+    #[test]
+    fn test_write_new_does_not_overwrite() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+
+        // another process creates the file right before the write step
+        std::fs::write(&path, r#"{ "foo": "other", "bar": false, "baz": 1 }"#).unwrap();
+
+        assert!(!write_new(&TestConfig::default(), &path, false).unwrap());
+
+        let loaded = TestConfig::load(&path).unwrap();
+        assert_eq!(loaded.foo, "other");
+    }
+
+    #[test]
+    fn test_write_new_failed_write() {
+        #[derive(serde::Serialize)]
+        struct Failing;
+
+        impl File for Failing {
+            fn load(_: impl AsRef<Path>) -> crate::Result<Self> {
+                Ok(Failing)
+            }
+
+            fn write_path(&self, _: &Path) -> crate::Result<()> {
+                Err(crate::error::Error::Custom("write failed"))
+            }
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+
+        assert!(write_new(&Failing, &path, false).is_err());
+        // neither the config nor the temporary file is left behind
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        assert!(write_new(&TestConfig::default(), &path, false).unwrap());
+        assert_eq!(TestConfig::load(&path).unwrap(), TestConfig::default());
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_init_keeps_existing_config() {
+        let home = TempHome::new();
+        let path = home.config_dir().join("my-app").join("config.json");
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, r#"{ "foo": "other", "bar": false, "baz": 1 }"#).unwrap();
+
+        assert_eq!(
+            init(TestConfig::default(), "my-app", "config.json").unwrap(),
+            path
+        );
+        assert_eq!(TestConfig::load(&path).unwrap().foo, "other");
+    }
+
+    #[cfg(all(feature = "directories", not(any(windows, target_os = "macos"))))]
+    #[test]
+    fn test_init_project() {
+        let home = TempHome::new();
+
+        let expected = directories::ProjectDirs::from("com", "Acme", "My App")
+            .unwrap()
+            .config_dir()
+            .join("config.json");
+
+        assert_eq!(
+            locate_config_project("com", "Acme", "My App", "config.json"),
+            None
+        );
+
+        let path = init_project(
+            TestConfig::default(),
+            "com",
+            "Acme",
+            "My App",
+            "config.json",
+        )
+        .unwrap();
+
+        assert_eq!(path, expected);
+        assert!(path.starts_with(home.config_dir()));
+        assert_eq!(
+            locate_config_project("com", "Acme", "My App", "config.json"),
+            Some(expected)
+        );
+    }
+}
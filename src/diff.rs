@@ -0,0 +1,200 @@
+/*!
+  This module contains the utilities to compare configs.
+*/
+
+use serde_json::Value;
+use std::path::Path;
+
+use crate::value;
+
+/// A single difference between two configs, `path` is the dotted key path (e.g. `server.port`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigDiff {
+    /// The key only exists in the new config
+    Added { path: String, value: Value },
+    /// The key only exists in the old config
+    Removed { path: String, value: Value },
+    /// The key exists in both configs with different values
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl ConfigDiff {
+    /// The dotted path of the key
+    pub fn path(&self) -> &str {
+        match self {
+            ConfigDiff::Added { path, .. }
+            | ConfigDiff::Removed { path, .. }
+            | ConfigDiff::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Compute the differences going from `a` to `b`, sorted by path
+///
+/// Objects are compared key by key, any other value (arrays included) as a whole.
+pub fn diff_values(a: &Value, b: &Value) -> Vec<ConfigDiff> {
+    let mut diffs = Vec::new();
+    collect(a, b, "", &mut diffs);
+
+    diffs
+}
+
+/// Compute the differences going from the config at `path_a` to the one at `path_b`
+pub fn diff_files(
+    path_a: impl AsRef<Path>,
+    path_b: impl AsRef<Path>,
+) -> crate::Result<Vec<ConfigDiff>> {
+    let a = value::load_value(path_a)?;
+    let b = value::load_value(path_b)?;
+
+    Ok(diff_values(&a, &b))
+}
+
+/// Render the change of the config at `path` to `new_config` as a unified diff,
+/// e.g. to preview it before writing
+///
+/// `new_config` is serialized in the format of `path` (detected from its extension),
+/// a missing file is diffed as an empty one. The diff is empty when nothing changes.
+#[cfg(feature = "text-diff")]
+pub fn diff_text<T>(path: impl AsRef<Path>, new_config: &T) -> crate::Result<String>
+where
+    T: serde::Serialize + ?Sized,
+{
+    let path = path.as_ref();
+    let new = crate::format::Format::for_path(path)?.serialize(new_config)?;
+
+    let old = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err.into()),
+    };
+
+    if old == new {
+        return Ok(String::new());
+    }
+
+    let name = path.display().to_string();
+
+    Ok(similar::TextDiff::from_lines(&old, &new)
+        .unified_diff()
+        .header(&name, &name)
+        .to_string())
+}
+
+fn join(parent: &str, key: &str) -> String {
+    match parent {
+        "" => key.to_string(),
+        _ => format!("{parent}.{key}"),
+    }
+}
+
+fn collect(a: &Value, b: &Value, path: &str, diffs: &mut Vec<ConfigDiff>) {
+    match (a, b) {
+        (Value::Object(a), Value::Object(b)) => {
+            let mut keys: Vec<_> = a.keys().chain(b.keys()).collect();
+            keys.sort();
+            keys.dedup();
+
+            for key in keys {
+                let path = join(path, key);
+
+                match (a.get(key), b.get(key)) {
+                    (Some(a), Some(b)) => collect(a, b, &path, diffs),
+                    (Some(value), None) => diffs.push(ConfigDiff::Removed {
+                        path,
+                        value: value.clone(),
+                    }),
+                    (None, Some(value)) => diffs.push(ConfigDiff::Added {
+                        path,
+                        value: value.clone(),
+                    }),
+                    (None, None) => unreachable!("key comes from one of the maps"),
+                }
+            }
+        }
+        (a, b) if a != b => diffs.push(ConfigDiff::Changed {
+            path: path.to_string(),
+            old: a.clone(),
+            new: b.clone(),
+        }),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_values() {
+        let a =
+            json!({ "name": "app", "debug": true, "server": { "host": "localhost", "port": 80 } });
+        let b = json!({ "name": "app", "verbose": true, "server": { "host": "localhost", "port": 8080 } });
+
+        assert_eq!(
+            diff_values(&a, &b),
+            vec![
+                ConfigDiff::Removed {
+                    path: "debug".to_string(),
+                    value: json!(true),
+                },
+                ConfigDiff::Changed {
+                    path: "server.port".to_string(),
+                    old: json!(80),
+                    new: json!(8080),
+                },
+                ConfigDiff::Added {
+                    path: "verbose".to_string(),
+                    value: json!(true),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_equal_values() {
+        let a = json!({ "name": "app", "list": [1, 2] });
+
+        assert!(diff_values(&a, &a.clone()).is_empty());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_diff_files() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let a = dir.path().join("a.json");
+        let b = dir.path().join("b.json");
+        std::fs::write(&a, r#"{ "foo": "a", "bar": true }"#).unwrap();
+        std::fs::write(&b, r#"{ "foo": "b", "bar": true }"#).unwrap();
+
+        let diffs = diff_files(&a, &b).unwrap();
+        assert_eq!(diffs.len(), 1);
+        assert_eq!(diffs[0].path(), "foo");
+    }
+
+    #[cfg(all(feature = "text-diff", feature = "json"))]
+    #[test]
+    fn test_diff_text() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        let config = crate::test_utils::TestConfig::default();
+        crate::format::write_auto(&config, &path).unwrap();
+
+        assert_eq!(diff_text(&path, &config).unwrap(), "");
+
+        let changed = crate::test_utils::TestConfig { baz: 7, ..config };
+        let diff = diff_text(&path, &changed).unwrap();
+
+        assert!(diff.starts_with("--- "), "{diff}");
+        assert!(diff.contains("+++ "), "{diff}");
+        assert!(diff.contains("-  \"baz\": 42\n"), "{diff}");
+        assert!(diff.contains("+  \"baz\": 7\n"), "{diff}");
+        // unchanged lines are context
+        assert!(diff.contains("   \"foo\": \"foo\",\n"), "{diff}");
+    }
+}
@@ -0,0 +1,156 @@
+/*!
+  This module contains helpers to control how unknown
+  enum variants are handled while loading a config.
+
+  Fields opting in via `#[serde(deserialize_with = "cli_config::enums::or_default")]`
+  fall back to their `Default` value when loaded through [`load_lenient`],
+  and report the offending variant when loaded through [`load_strict`].
+*/
+
+use serde::{de::Error as _, Deserialize, Deserializer};
+use serde_json::Value;
+use std::{cell::Cell, path::Path};
+
+use crate::fs::File;
+
+thread_local! {
+    static LENIENT: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Restores the previous leniency once dropped
+struct LenientGuard(bool);
+
+impl LenientGuard {
+    fn set(lenient: bool) -> Self {
+        Self(LENIENT.with(|cell| cell.replace(lenient)))
+    }
+}
+
+impl Drop for LenientGuard {
+    fn drop(&mut self) {
+        LENIENT.with(|cell| cell.set(self.0));
+    }
+}
+
+/// Load the config, failing on unknown enum variants.
+///
+/// This is the default behaviour of [`File::load`], this function makes it explicit.
+pub fn load_strict<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: File,
+{
+    let _guard = LenientGuard::set(false);
+
+    T::load(path)
+}
+
+/// Load the config, replacing unknown enum variants with their default
+/// on fields annotated with [`or_default`].
+pub fn load_lenient<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: File,
+{
+    let _guard = LenientGuard::set(true);
+
+    T::load(path)
+}
+
+/// Deserialize an enum field, falling back to `T::default()` for unknown
+/// variants when loading through [`load_lenient`].
+///
+/// ```
+/// #[derive(serde::Deserialize, Default)]
+/// enum Theme {
+///     #[default]
+///     Light,
+///     Dark,
+/// }
+///
+/// #[derive(serde::Deserialize)]
+/// struct MyConfig {
+///     #[serde(deserialize_with = "cli_config::enums::or_default")]
+///     pub theme: Theme,
+/// }
+/// ```
+pub fn or_default<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: serde::de::DeserializeOwned + Default,
+{
+    let value = Value::deserialize(deserializer)?;
+    let is_unit_variant = value.is_string();
+
+    match T::deserialize(value) {
+        Ok(value) => Ok(value),
+        Err(_) if is_unit_variant && LENIENT.with(Cell::get) => Ok(T::default()),
+        Err(err) => Err(D::Error::custom(err)),
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Serialize;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Theme {
+        #[default]
+        Light,
+        Dark,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct ThemeConfig {
+        #[serde(deserialize_with = "or_default")]
+        theme: Theme,
+    }
+
+    impl File for ThemeConfig {
+        fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+            let content = fs::read_to_string(path)?;
+
+            Ok(serde_json::from_str(&content)?)
+        }
+
+        fn write_path(&self, path: &Path) -> crate::Result<()> {
+            fs::write(path, serde_json::to_string(self)?)?;
+
+            Ok(())
+        }
+    }
+
+    fn write_config(dir: &TempDir, content: &str) -> std::path::PathBuf {
+        let path = dir.path().join("config.json");
+        fs::write(&path, content).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_load_lenient() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write_config(&dir, r#"{ "theme": "purple" }"#);
+
+        let config: ThemeConfig = load_lenient(&path).unwrap();
+        assert_eq!(config.theme, Theme::Light);
+
+        // known variants are still honoured
+        let path = write_config(&dir, r#"{ "theme": "dark" }"#);
+        let config: ThemeConfig = load_lenient(&path).unwrap();
+        assert_eq!(config.theme, Theme::Dark);
+    }
+
+    #[test]
+    fn test_load_strict() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write_config(&dir, r#"{ "theme": "purple" }"#);
+
+        let err = load_strict::<ThemeConfig>(&path).unwrap_err().to_string();
+        assert!(err.contains("purple"), "{err}");
+        assert!(err.contains("light") && err.contains("dark"), "{err}");
+    }
+}
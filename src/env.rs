@@ -0,0 +1,521 @@
+/*!
+  This module contains the utilities to read
+  config values from the environment.
+*/
+
+use serde::de::{
+    value::MapDeserializer, DeserializeOwned, Deserializer, IntoDeserializer, Visitor,
+};
+use serde_json::Value;
+use std::path::Path;
+
+use crate::{error::Error, value};
+
+/// What to do with a reference to an undefined environment variable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UndefinedVar {
+    /// Leave the reference untouched (e.g. `${MISSING}` stays as-is)
+    #[default]
+    Keep,
+    /// Fail with `Error::UndefinedVariable`
+    Error,
+}
+
+/// Load the config at `path` expanding `${VAR}` and `$VAR` references
+/// inside string values before deserializing it into `T`.
+///
+/// Use `$$` to write a literal `$`. Keys are never expanded.
+pub fn load_interpolated<T>(path: impl AsRef<Path>, undefined: UndefinedVar) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut config = value::load_value(path)?;
+    interpolate(&mut config, undefined)?;
+
+    value::from_value(config)
+}
+
+/// Load the config at `path` filling the top-level fields absent from the file
+/// from their environment variable, `fields` being a list of `(field, var)`
+/// (e.g. `("token", "MYAPP_TOKEN")`).
+///
+/// A field present in the file is never overridden. Each variable is read as
+/// a string, unless its field expects another type (e.g. a number or a boolean)
+/// in which case its JSON value is used (e.g. `8080` or `true`).
+pub fn load_with_field_env<T>(path: impl AsRef<Path>, fields: &[(&str, &str)]) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let config = value::load_value(path)?;
+    let Value::Object(map) = config else {
+        return value::from_value(config);
+    };
+
+    let mut entries: Vec<_> = map
+        .into_iter()
+        .map(|(key, value)| (key, FieldValue::File(value)))
+        .collect();
+
+    for (field, var) in fields {
+        if entries.iter().any(|(key, _)| key == field) {
+            continue;
+        }
+
+        if let Some(raw) = std::env::var_os(var) {
+            let raw = raw
+                .into_string()
+                .map_err(|_| Error::InvalidConfig("non UTF-8 environment variable"))?;
+            entries.push((field.to_string(), FieldValue::Env(raw)));
+        }
+    }
+
+    crate::fs::deserialize(
+        MapDeserializer::<_, serde_json::Error>::new(entries.into_iter()),
+        Error::JSON,
+    )
+}
+
+/// A top-level field read by [`load_with_field_env`], the type of a variable
+/// being decided by what its field expects
+enum FieldValue {
+    File(Value),
+    Env(String),
+}
+
+/// Forward to the [`Value`] of the field, a variable being parsed as JSON
+macro_rules! forward_parsed {
+    ($($method:ident($($arg:ident: $ty:ty),*))*) => {
+        $(
+            fn $method<V>(self, $($arg: $ty,)* visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                let value = match self {
+                    FieldValue::File(value) => value,
+                    FieldValue::Env(raw) => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+                };
+
+                value.$method($($arg,)* visitor)
+            }
+        )*
+    };
+}
+
+/// Keep a variable as a string, used for the fields expecting one
+macro_rules! forward_string {
+    ($($method:ident)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                match self {
+                    FieldValue::File(value) => value.$method(visitor),
+                    FieldValue::Env(raw) => visitor.visit_string(raw),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for FieldValue {
+    type Error = serde_json::Error;
+
+    forward_string! {
+        deserialize_any deserialize_str deserialize_string deserialize_char deserialize_identifier
+    }
+
+    forward_parsed! {
+        deserialize_bool() deserialize_i8() deserialize_i16() deserialize_i32() deserialize_i64()
+        deserialize_i128() deserialize_u8() deserialize_u16() deserialize_u32() deserialize_u64()
+        deserialize_u128() deserialize_f32() deserialize_f64() deserialize_bytes()
+        deserialize_byte_buf() deserialize_unit() deserialize_unit_struct(name: &'static str)
+        deserialize_seq() deserialize_tuple(len: usize)
+        deserialize_tuple_struct(name: &'static str, len: usize) deserialize_map()
+        deserialize_struct(name: &'static str, fields: &'static [&'static str])
+        deserialize_enum(name: &'static str, variants: &'static [&'static str])
+        deserialize_ignored_any()
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            FieldValue::File(value) => value.deserialize_option(visitor),
+            // the type of the inner value decides
+            FieldValue::Env(_) => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            FieldValue::File(value) => value.deserialize_newtype_struct(name, visitor),
+            FieldValue::Env(_) => visitor.visit_newtype_struct(self),
+        }
+    }
+}
+
+impl IntoDeserializer<'_, serde_json::Error> for FieldValue {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+/// Load the config at `path` replacing every string of the form `${file:PATH}`
+/// with the content of the file at `PATH`, trimmed, before deserializing
+/// it into `T` (e.g. for secrets mounted as files)
+///
+/// A relative `PATH` is resolved against the directory of the config.
+/// A missing referenced file fails with `Error::FileNotFound` in that file.
+pub fn load_with_file_refs<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let mut config = value::load_value(path)?;
+    let base = path.parent().unwrap_or(Path::new(""));
+
+    resolve_file_refs(&mut config, base)?;
+
+    value::from_value(config)
+}
+
+/// Replace the `${file:PATH}` strings of `value`, relative to `base`
+fn resolve_file_refs(value: &mut Value, base: &Path) -> crate::Result<()> {
+    match value {
+        Value::String(s) => {
+            let Some(reference) = s
+                .strip_prefix("${file:")
+                .and_then(|rest| rest.strip_suffix('}'))
+            else {
+                return Ok(());
+            };
+
+            let path = base.join(reference);
+            let content = match std::fs::read_to_string(&path) {
+                Ok(content) => content,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                    return Err(Error::FileNotFound.in_file(path))
+                }
+                Err(err) => return Err(Error::FileSystem(err).in_file(path)),
+            };
+
+            *s = content.trim().to_string();
+        }
+        Value::Array(items) => {
+            for item in items {
+                resolve_file_refs(item, base)?;
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                resolve_file_refs(item, base)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Expand environment variable references in every string of `value`
+pub fn interpolate(value: &mut Value, undefined: UndefinedVar) -> crate::Result<()> {
+    match value {
+        Value::String(s) => *s = expand(s, undefined)?,
+        Value::Array(items) => {
+            for item in items {
+                interpolate(item, undefined)?;
+            }
+        }
+        Value::Object(map) => {
+            for item in map.values_mut() {
+                interpolate(item, undefined)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Expand environment variable references in `input`
+pub fn expand(input: &str, undefined: UndefinedVar) -> crate::Result<String> {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(idx) = rest.find('$') {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx + 1..];
+
+        if let Some(after) = rest.strip_prefix('$') {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+
+        let (name, reference, after) = match rest.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(end) => (&braced[..end], &rest[..end + 2], &braced[end + 1..]),
+                None => ("", "", rest),
+            },
+            None => {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+
+                (&rest[..end], &rest[..end], &rest[end..])
+            }
+        };
+
+        if name.is_empty() {
+            // not a reference, keep the `$` as-is
+            output.push('$');
+            continue;
+        }
+
+        match std::env::var(name) {
+            Ok(value) => output.push_str(&value),
+            Err(_) if undefined == UndefinedVar::Keep => {
+                output.push('$');
+                output.push_str(reference);
+            }
+            Err(_) => return Err(Error::UndefinedVariable(name.to_string())),
+        }
+
+        rest = after;
+    }
+
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::lock_env;
+
+    #[test]
+    fn test_expand_defined() {
+        let _env = lock_env();
+        std::env::set_var("CLI_CONFIG_TEST_DIR", "/tmp/data");
+
+        assert_eq!(
+            expand("${CLI_CONFIG_TEST_DIR}/db", UndefinedVar::Error).unwrap(),
+            "/tmp/data/db"
+        );
+        assert_eq!(
+            expand("$CLI_CONFIG_TEST_DIR/db", UndefinedVar::Error).unwrap(),
+            "/tmp/data/db"
+        );
+
+        std::env::remove_var("CLI_CONFIG_TEST_DIR");
+    }
+
+    #[test]
+    fn test_expand_undefined() {
+        let _env = lock_env();
+
+        assert_eq!(
+            expand("${CLI_CONFIG_TEST_MISSING}/db", UndefinedVar::Keep).unwrap(),
+            "${CLI_CONFIG_TEST_MISSING}/db"
+        );
+        assert_eq!(
+            expand("$CLI_CONFIG_TEST_MISSING/db", UndefinedVar::Keep).unwrap(),
+            "$CLI_CONFIG_TEST_MISSING/db"
+        );
+        assert!(matches!(
+            expand("${CLI_CONFIG_TEST_MISSING}", UndefinedVar::Error),
+            Err(Error::UndefinedVariable(name)) if name == "CLI_CONFIG_TEST_MISSING"
+        ));
+    }
+
+    #[test]
+    fn test_expand_escape() {
+        assert_eq!(
+            expand("price: $$5, ${unclosed", UndefinedVar::Error).unwrap(),
+            "price: $5, ${unclosed"
+        );
+        assert_eq!(expand("$$HOME", UndefinedVar::Error).unwrap(), "$HOME");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_load_interpolated() {
+        let _env = lock_env();
+        std::env::set_var("CLI_CONFIG_TEST_NAME", "interpolated");
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "foo": "${CLI_CONFIG_TEST_NAME}", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+
+        let config: crate::test_utils::TestConfig =
+            load_interpolated(&path, UndefinedVar::Error).unwrap();
+        assert_eq!(config.foo, "interpolated");
+
+        std::env::remove_var("CLI_CONFIG_TEST_NAME");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_load_with_file_refs() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let secret = dir.path().join("secret");
+        std::fs::write(&secret, "s3cr3t\n").unwrap();
+
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "foo": "${file:secret}", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+
+        let config: crate::test_utils::TestConfig = load_with_file_refs(&path).unwrap();
+        assert_eq!(config.foo, "s3cr3t");
+
+        // absolute paths are used as-is
+        let reference = format!("${{file:{}}}", secret.display());
+        std::fs::write(
+            &path,
+            serde_json::json!({ "foo": reference, "bar": true, "baz": 1 }).to_string(),
+        )
+        .unwrap();
+        let config: crate::test_utils::TestConfig = load_with_file_refs(&path).unwrap();
+        assert_eq!(config.foo, "s3cr3t");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_load_with_missing_file_ref() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "foo": "${file:missing}", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+
+        let err = load_with_file_refs::<crate::test_utils::TestConfig>(&path).unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::InFile { path, source }
+                if path.ends_with("missing") && matches!(**source, Error::FileNotFound)
+        ));
+    }
+
+    #[cfg(feature = "json")]
+    mod field_env {
+        use super::*;
+        use crate::test_utils::TestConfig;
+        use tempdir::TempDir;
+
+        const FIELDS: &[(&str, &str)] = &[
+            ("foo", "CLI_CONFIG_TEST_FOO"),
+            ("baz", "CLI_CONFIG_TEST_BAZ"),
+        ];
+
+        fn load(content: &str) -> TestConfig {
+            let dir = TempDir::new("test_config").unwrap();
+            let path = dir.path().join("config.json");
+            std::fs::write(&path, content).unwrap();
+
+            load_with_field_env(&path, FIELDS).unwrap()
+        }
+
+        #[test]
+        fn test_absent_from_file() {
+            let _env = lock_env();
+            std::env::set_var("CLI_CONFIG_TEST_FOO", "from-env");
+            std::env::set_var("CLI_CONFIG_TEST_BAZ", "7");
+
+            let config = load(r#"{ "bar": true }"#);
+            assert_eq!(config.foo, "from-env");
+            assert_eq!(config.baz, 7);
+
+            std::env::remove_var("CLI_CONFIG_TEST_FOO");
+            std::env::remove_var("CLI_CONFIG_TEST_BAZ");
+        }
+
+        #[test]
+        fn test_present_in_file() {
+            let _env = lock_env();
+            std::env::set_var("CLI_CONFIG_TEST_FOO", "from-env");
+            std::env::set_var("CLI_CONFIG_TEST_BAZ", "7");
+
+            let config = load(r#"{ "foo": "from-file", "bar": true, "baz": 1 }"#);
+            assert_eq!(config.foo, "from-file");
+            assert_eq!(config.baz, 1);
+
+            std::env::remove_var("CLI_CONFIG_TEST_FOO");
+            std::env::remove_var("CLI_CONFIG_TEST_BAZ");
+        }
+
+        #[test]
+        fn test_missing_variable() {
+            let _env = lock_env();
+            std::env::remove_var("CLI_CONFIG_TEST_FOO");
+
+            let dir = TempDir::new("test_config").unwrap();
+            let path = dir.path().join("config.json");
+            std::fs::write(&path, r#"{ "bar": true, "baz": 1 }"#).unwrap();
+
+            // still missing, as if there was no fallback
+            assert!(load_with_field_env::<TestConfig>(&path, FIELDS).is_err());
+        }
+
+        #[test]
+        fn test_type_per_field() {
+            #[derive(Debug, serde::Deserialize)]
+            struct Service {
+                token: String,
+                port: u16,
+                timeout: Option<u32>,
+                name: Option<String>,
+            }
+
+            let _env = lock_env();
+            std::env::set_var("CLI_CONFIG_TEST_TOKEN", "12345");
+            std::env::set_var("CLI_CONFIG_TEST_PORT", "8080");
+            std::env::set_var("CLI_CONFIG_TEST_TIMEOUT", "30");
+            std::env::set_var("CLI_CONFIG_TEST_NAME", "true");
+
+            let dir = TempDir::new("test_config").unwrap();
+            let path = dir.path().join("config.json");
+            std::fs::write(&path, "{}").unwrap();
+
+            let config: Service = load_with_field_env(
+                &path,
+                &[
+                    ("token", "CLI_CONFIG_TEST_TOKEN"),
+                    ("port", "CLI_CONFIG_TEST_PORT"),
+                    ("timeout", "CLI_CONFIG_TEST_TIMEOUT"),
+                    ("name", "CLI_CONFIG_TEST_NAME"),
+                ],
+            )
+            .unwrap();
+
+            for var in ["TOKEN", "PORT", "TIMEOUT", "NAME"] {
+                std::env::remove_var(format!("CLI_CONFIG_TEST_{var}"));
+            }
+
+            assert_eq!(config.token, "12345");
+            assert_eq!(config.port, 8080);
+            assert_eq!(config.timeout, Some(30));
+            assert_eq!(config.name.as_deref(), Some("true"));
+        }
+    }
+}
@@ -6,14 +6,59 @@ pub enum Error {
     #[error("invalid config: {0}")]
     InvalidConfig(&'static str),
 
-    #[cfg(feature = "json")]
+    #[error("invalid config: {0}")]
+    Validation(String),
+
+    #[error("include cycle detected at {}", .0.display())]
+    IncludeCycle(std::path::PathBuf),
+
+    #[error("section not found: {0}")]
+    SectionNotFound(String),
+
+    #[error("profile not found: {0}")]
+    ProfileNotFound(String),
+
+    #[error("key not found: {0}")]
+    KeyNotFound(String),
+
+    #[error("invalid type for `{key}`: expected {expected}, found {found}")]
+    InvalidType {
+        key: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    #[error("immutable field `{0}` cannot be changed")]
+    ImmutableField(String),
+
+    #[error("config version {found} is newer than supported {supported}")]
+    UnsupportedVersion { found: u64, supported: u64 },
+
+    #[error("config file is empty: {}", .0.display())]
+    EmptyConfig(std::path::PathBuf),
+
     #[error("invalid json: {0}")]
     JSON(#[from] serde_json::Error),
 
+    #[cfg(feature = "hjson")]
+    #[error("invalid hjson: {0}")]
+    HJSON(#[from] deser_hjson::Error),
+
     #[cfg(feature = "yaml")]
     #[error("invalid yaml: {0}")]
     YAML(#[from] serde_yaml::Error),
 
+    #[cfg(feature = "yaml")]
+    #[error(
+        "unknown YAML tag `{tag}` at line {line} (expected one of: {})",
+        expected.join(", ")
+    )]
+    UnknownTag {
+        tag: String,
+        expected: Vec<String>,
+        line: usize,
+    },
+
     #[cfg(feature = "toml")]
     #[error("invalid toml: {0}")]
     TOML(#[from] toml::de::Error),
@@ -22,15 +67,508 @@ pub enum Error {
     #[error("cannot serialize: {0}")]
     TomlWrite(#[from] toml::ser::Error),
 
+    #[cfg(feature = "toml-preserve")]
+    #[error("cannot serialize: {0}")]
+    TomlEditWrite(#[from] toml_edit::ser::Error),
+
+    #[cfg(feature = "toml-preserve")]
+    #[error("invalid toml: {0}")]
+    TomlEdit(#[from] toml_edit::TomlError),
+
+    #[error("{}: {source}", path.display())]
+    InFile {
+        path: std::path::PathBuf,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[cfg(feature = "path-to-error")]
+    #[error("{path}: {source}")]
+    AtPath {
+        path: String,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[error("{source}\nhint: {hint}")]
+    WithHint {
+        hint: std::borrow::Cow<'static, str>,
+        #[source]
+        source: Box<Error>,
+    },
+
+    #[cfg(feature = "zip")]
+    #[error("invalid archive: {0}")]
+    Zip(#[from] zip::result::ZipError),
+
+    #[cfg(feature = "zip")]
+    #[error("archive entry not found: {0}")]
+    EntryNotFound(String),
+
+    #[cfg(feature = "http")]
+    #[error("request to {url} failed with status {status}")]
+    HttpStatus { url: String, status: u16 },
+
+    #[cfg(feature = "http")]
+    #[error("http request failed: {0}")]
+    Http(String),
+
+    #[error("permission denied: {}", .0.display())]
+    PermissionDenied(std::path::PathBuf),
+
+    #[cfg(not(windows))]
+    #[error("cannot resolve the config directories: {0}")]
+    BaseDirectories(#[from] xdg::BaseDirectoriesError),
+
+    #[cfg(not(windows))]
+    #[error("invalid $XDG_CONFIG_HOME `{}`: must be an absolute path", .0.display())]
+    InvalidXdgConfigHome(std::path::PathBuf),
+
     #[error("FileSystem error")]
     FileSystem(#[from] std::io::Error),
 
     #[error("the theme you are looking for does not exists")]
     ThemeNotFound,
 
+    #[error("undefined environment variable: {0}")]
+    UndefinedVariable(String),
+
+    #[error("shell variable `{name}` is set by both `{first}` and `{second}`")]
+    ShellVarCollision {
+        name: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("unresolved template placeholders: {}", .0.join(", "))]
+    UnresolvedPlaceholders(Vec<String>),
+
+    #[error("{0}")]
+    EnvVar(#[from] std::env::VarError),
+
+    #[error("invalid path: {0}")]
+    StripPrefix(#[from] std::path::StripPrefixError),
+
+    #[error(
+        "unsupported config extension `{extension}` (supported formats: {})",
+        join_formats(supported)
+    )]
+    UnsupportedExtension {
+        extension: String,
+        supported: Vec<crate::format::Format>,
+    },
+
+    #[error("{0} support not compiled in")]
+    FormatDisabled(crate::format::Format),
+
     #[error("{0}")]
     Custom(&'static str),
 
     #[error("something went wrong: {0}")]
     Generic(#[from] anyhow::Error),
 }
+
+fn join_formats(formats: &[crate::format::Format]) -> String {
+    if formats.is_empty() {
+        return "none".to_string();
+    }
+
+    formats
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl Error {
+    /// Get the underlying IO error, if any
+    pub fn io_error(&self) -> Option<&std::io::Error> {
+        match self {
+            Error::FileSystem(err) => Some(err),
+            Error::InFile { source, .. } | Error::WithHint { source, .. } => source.io_error(),
+            #[cfg(feature = "path-to-error")]
+            Error::AtPath { source, .. } => source.io_error(),
+            _ => None,
+        }
+    }
+
+    /// Get the underlying `serde_json` error, if any (e.g. to read its line/column)
+    pub fn json_error(&self) -> Option<&serde_json::Error> {
+        match self {
+            Error::JSON(err) => Some(err),
+            Error::InFile { source, .. } | Error::WithHint { source, .. } => source.json_error(),
+            #[cfg(feature = "path-to-error")]
+            Error::AtPath { source, .. } => source.json_error(),
+            _ => None,
+        }
+    }
+
+    /// Get the underlying (de)serialization error of any format, if any
+    pub fn source_serde(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::JSON(err) => Some(err),
+            #[cfg(feature = "hjson")]
+            Error::HJSON(err) => Some(err),
+            #[cfg(feature = "yaml")]
+            Error::YAML(err) => Some(err),
+            #[cfg(feature = "toml")]
+            Error::TOML(err) => Some(err),
+            #[cfg(feature = "toml")]
+            Error::TomlWrite(err) => Some(err),
+            #[cfg(feature = "toml-preserve")]
+            Error::TomlEditWrite(err) => Some(err),
+            #[cfg(feature = "toml-preserve")]
+            Error::TomlEdit(err) => Some(err),
+            Error::InFile { source, .. } | Error::WithHint { source, .. } => source.source_serde(),
+            #[cfg(feature = "path-to-error")]
+            Error::AtPath { source, .. } => source.source_serde(),
+            _ => None,
+        }
+    }
+
+    /// Build the error of a failed validation, e.g. from a custom validator
+    /// including the offending value in `message`
+    ///
+    /// ```
+    /// use cli_config::error::Error;
+    ///
+    /// let port = 70000;
+    /// let err = Error::validation(format!("port {port} out of range 1..=65535"));
+    /// assert_eq!(err.to_string(), "invalid config: port 70000 out of range 1..=65535");
+    /// ```
+    pub fn validation(message: impl Into<String>) -> Self {
+        Error::Validation(message.into())
+    }
+
+    /// Attach a user-facing hint (e.g. "run `myapp init` to create it"),
+    /// displayed on a second line
+    pub fn with_hint(self, hint: impl Into<std::borrow::Cow<'static, str>>) -> Self {
+        Error::WithHint {
+            hint: hint.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// Get the hint attached with [`Error::with_hint`], if any
+    pub fn hint(&self) -> Option<&str> {
+        match self {
+            Error::WithHint { hint, .. } => Some(hint),
+            Error::InFile { source, .. } => source.hint(),
+            #[cfg(feature = "path-to-error")]
+            Error::AtPath { source, .. } => source.hint(),
+            _ => None,
+        }
+    }
+
+    /// Attach the path of the file that caused the error
+    pub fn in_file(self, path: impl Into<std::path::PathBuf>) -> Self {
+        Error::InFile {
+            path: path.into(),
+            source: Box::new(self),
+        }
+    }
+}
+
+/// A machine-readable view of an [`Error`], e.g. to print it as JSON
+///
+/// With the `serde-errors` feature enabled it implements `serde::Serialize`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-errors", derive(serde::Serialize))]
+pub struct ErrorReport {
+    /// Stable identifier of the error variant (e.g. `file_not_found`)
+    pub kind: &'static str,
+    /// Human readable description
+    pub message: String,
+    /// File the error refers to, if known
+    #[cfg_attr(
+        feature = "serde-errors",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub path: Option<std::path::PathBuf>,
+    /// Line of a parsing error, if known
+    #[cfg_attr(
+        feature = "serde-errors",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub line: Option<usize>,
+    /// Column of a parsing error, if known
+    #[cfg_attr(
+        feature = "serde-errors",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub column: Option<usize>,
+    /// User-facing hint attached with [`Error::with_hint`]
+    #[cfg_attr(
+        feature = "serde-errors",
+        serde(skip_serializing_if = "Option::is_none")
+    )]
+    pub hint: Option<String>,
+}
+
+impl Error {
+    /// Stable identifier of the error variant
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::FileNotFound => "file_not_found",
+            Error::InvalidConfig(_) | Error::Validation(_) => "invalid_config",
+            Error::IncludeCycle(_) => "include_cycle",
+            Error::SectionNotFound(_) => "section_not_found",
+            Error::ProfileNotFound(_) => "profile_not_found",
+            Error::KeyNotFound(_) => "key_not_found",
+            Error::InvalidType { .. } => "invalid_type",
+            Error::ImmutableField(_) => "immutable_field",
+            Error::UnsupportedVersion { .. } => "unsupported_version",
+            Error::EmptyConfig(_) => "empty_config",
+            Error::JSON(_) => "json",
+            #[cfg(feature = "hjson")]
+            Error::HJSON(_) => "hjson",
+            #[cfg(feature = "yaml")]
+            Error::YAML(_) => "yaml",
+            #[cfg(feature = "yaml")]
+            Error::UnknownTag { .. } => "unknown_tag",
+            #[cfg(feature = "toml")]
+            Error::TOML(_) => "toml",
+            #[cfg(feature = "toml")]
+            Error::TomlWrite(_) => "toml_write",
+            #[cfg(feature = "toml-preserve")]
+            Error::TomlEditWrite(_) => "toml_write",
+            #[cfg(feature = "toml-preserve")]
+            Error::TomlEdit(_) => "toml",
+            Error::InFile { source, .. } | Error::WithHint { source, .. } => source.kind(),
+            #[cfg(feature = "path-to-error")]
+            Error::AtPath { source, .. } => source.kind(),
+            #[cfg(feature = "zip")]
+            Error::Zip(_) => "archive",
+            #[cfg(feature = "zip")]
+            Error::EntryNotFound(_) => "entry_not_found",
+            #[cfg(feature = "http")]
+            Error::HttpStatus { .. } => "http_status",
+            #[cfg(feature = "http")]
+            Error::Http(_) => "http",
+            Error::PermissionDenied(_) => "permission_denied",
+            #[cfg(not(windows))]
+            Error::BaseDirectories(_) => "base_directories",
+            #[cfg(not(windows))]
+            Error::InvalidXdgConfigHome(_) => "invalid_xdg_config_home",
+            Error::FileSystem(_) => "filesystem",
+            Error::ThemeNotFound => "theme_not_found",
+            Error::UndefinedVariable(_) => "undefined_variable",
+            Error::ShellVarCollision { .. } => "shell_var_collision",
+            Error::UnresolvedPlaceholders(_) => "unresolved_placeholders",
+            Error::EnvVar(_) => "env_var",
+            Error::StripPrefix(_) => "strip_prefix",
+            Error::UnsupportedExtension { .. } => "unsupported_extension",
+            Error::FormatDisabled(_) => "format_disabled",
+            Error::Custom(_) => "custom",
+            Error::Generic(_) => "generic",
+        }
+    }
+
+    /// Build the machine-readable [`ErrorReport`] of this error
+    pub fn report(&self) -> ErrorReport {
+        let mut report = match self {
+            Error::InFile { path, source } => {
+                let mut report = source.report();
+                report.path.get_or_insert_with(|| path.clone());
+                return report;
+            }
+            #[cfg(feature = "path-to-error")]
+            Error::AtPath { path, source } => {
+                let mut report = source.report();
+                report.message = format!("{path}: {}", report.message);
+                return report;
+            }
+            Error::WithHint { hint, source } => {
+                let mut report = source.report();
+                report.hint.get_or_insert_with(|| hint.to_string());
+                return report;
+            }
+            Error::FileSystem(err) => ErrorReport {
+                kind: self.kind(),
+                message: err.to_string(),
+                path: None,
+                line: None,
+                column: None,
+                hint: None,
+            },
+            _ => ErrorReport {
+                kind: self.kind(),
+                message: self.to_string(),
+                path: None,
+                line: None,
+                column: None,
+                hint: None,
+            },
+        };
+
+        match self {
+            Error::IncludeCycle(path)
+            | Error::EmptyConfig(path)
+            | Error::PermissionDenied(path) => {
+                report.path = Some(path.clone());
+            }
+            Error::JSON(err) if err.line() > 0 => {
+                report.line = Some(err.line());
+                report.column = Some(err.column());
+            }
+            #[cfg(feature = "yaml")]
+            Error::YAML(err) => {
+                if let Some(location) = err.location() {
+                    report.line = Some(location.line());
+                    report.column = Some(location.column());
+                }
+            }
+            #[cfg(feature = "yaml")]
+            Error::UnknownTag { line, .. } => report.line = Some(*line),
+            _ => {}
+        }
+
+        report
+    }
+}
+
+impl From<&Error> for ErrorReport {
+    fn from(err: &Error) -> Self {
+        err.report()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_io_error() {
+        let err = Error::from(std::io::Error::from(std::io::ErrorKind::NotFound));
+
+        assert_eq!(err.io_error().unwrap().kind(), std::io::ErrorKind::NotFound);
+        assert!(err.source_serde().is_none());
+
+        let err = err.in_file("config.json");
+        assert_eq!(err.io_error().unwrap().kind(), std::io::ErrorKind::NotFound);
+    }
+
+    #[test]
+    fn test_source_serde() {
+        let err = Error::from(serde_json::from_str::<u32>("\n  nope").unwrap_err());
+
+        assert!(err.io_error().is_none());
+        assert_eq!(err.json_error().unwrap().line(), 2);
+        assert!(err.source_serde().unwrap().to_string().contains("line 2"));
+
+        let err = err.in_file("config.json");
+        assert_eq!(err.json_error().unwrap().line(), 2);
+    }
+
+    #[test]
+    fn test_no_source() {
+        assert!(Error::FileNotFound.io_error().is_none());
+        assert!(Error::FileNotFound.source_serde().is_none());
+    }
+
+    #[test]
+    fn test_report() {
+        let report = Error::FileNotFound.report();
+        assert_eq!(report.kind, "file_not_found");
+        assert_eq!(report.path, None);
+
+        let err = Error::from(serde_json::from_str::<u32>("\n  nope").unwrap_err());
+        let report = err.in_file("config.json").report();
+        assert_eq!(report.kind, "json");
+        assert_eq!(report.path, Some("config.json".into()));
+        assert_eq!(report.line, Some(2));
+    }
+
+    #[cfg(feature = "serde-errors")]
+    #[test]
+    fn test_serialize_report() {
+        assert_eq!(
+            serde_json::to_value(Error::FileNotFound.report()).unwrap(),
+            serde_json::json!({
+                "kind": "file_not_found",
+                "message": "cannot find file",
+            })
+        );
+
+        let err = Error::from(serde_json::from_str::<u32>("nope").unwrap_err());
+        let message = err.to_string();
+
+        assert_eq!(
+            serde_json::to_value(err.in_file("config.json").report()).unwrap(),
+            serde_json::json!({
+                "kind": "json",
+                "message": message,
+                "path": "config.json",
+                "line": 1,
+                "column": 2,
+            })
+        );
+    }
+
+    #[test]
+    fn test_validation() {
+        let port = 70000;
+        let err = Error::validation(format!("port {port} out of range 1..=65535"));
+
+        assert!(matches!(&err, Error::Validation(message) if message.contains("70000")));
+        assert_eq!(
+            err.to_string(),
+            "invalid config: port 70000 out of range 1..=65535"
+        );
+        assert_eq!(err.kind(), Error::InvalidConfig("").kind());
+
+        let err = err.in_file("config.json");
+        assert_eq!(err.report().kind, "invalid_config");
+        assert_eq!(
+            err.report().message,
+            "invalid config: port 70000 out of range 1..=65535"
+        );
+    }
+
+    #[test]
+    fn test_conversions() {
+        fn relative(path: &str) -> crate::Result<std::path::PathBuf> {
+            Ok(std::path::Path::new(path)
+                .strip_prefix("/etc")?
+                .to_path_buf())
+        }
+
+        fn var() -> crate::Result<String> {
+            Ok(std::env::var("CLI_CONFIG_UNDEFINED_TEST_VAR")?)
+        }
+
+        assert_eq!(
+            relative("/etc/my-app").unwrap(),
+            std::path::Path::new("my-app")
+        );
+
+        let err = relative("/usr/my-app").unwrap_err();
+        assert!(matches!(err, Error::StripPrefix(_)));
+        assert_eq!(err.kind(), "strip_prefix");
+
+        let err = var().unwrap_err();
+        assert!(matches!(err, Error::EnvVar(std::env::VarError::NotPresent)));
+        assert_eq!(err.kind(), "env_var");
+        assert_eq!(err.to_string(), "environment variable not found");
+    }
+
+    #[test]
+    fn test_with_hint() {
+        let err = Error::FileNotFound.with_hint("run `myapp init` to create it");
+
+        assert_eq!(
+            err.to_string(),
+            "cannot find file\nhint: run `myapp init` to create it"
+        );
+        assert_eq!(err.hint(), Some("run `myapp init` to create it"));
+        assert_eq!(err.kind(), "file_not_found");
+        assert_eq!(
+            err.report().hint.as_deref(),
+            Some("run `myapp init` to create it")
+        );
+
+        let err = Error::FileNotFound.with_hint(format!("run `{} init`", "myapp"));
+        assert_eq!(err.hint(), Some("run `myapp init`"));
+        assert_eq!(Error::FileNotFound.hint(), None);
+    }
+}
@@ -0,0 +1,759 @@
+/*!
+  This module contains the supported config formats and
+  the helpers to (de)serialize them from/to strings.
+*/
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fmt, path::Path};
+
+use crate::error::Error;
+
+/// Config file formats known by the crate.
+///
+/// Every variant is always available, but (de)serializing requires
+/// the matching feature (`json`, `yaml` or `toml`) to be enabled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    /// All the formats known by the crate
+    pub const ALL: [Format; 3] = [Format::Json, Format::Yaml, Format::Toml];
+
+    /// The formats whose feature is enabled
+    pub fn enabled() -> Vec<Format> {
+        Format::ALL
+            .into_iter()
+            .filter(|format| format.is_enabled())
+            .collect()
+    }
+
+    /// Resolve the format of `path`, failing with `Error::UnsupportedExtension`
+    /// when the extension is unknown and with `Error::FormatDisabled` when
+    /// it's known but its feature is not enabled
+    pub fn for_path(path: impl AsRef<Path>) -> crate::Result<Format> {
+        let path = path.as_ref();
+
+        match Format::from_path(path) {
+            Some(format) if format.is_enabled() => Ok(format),
+            Some(format) => Err(Error::FormatDisabled(format)),
+            None => Err(Error::UnsupportedExtension {
+                extension: path
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                supported: Format::enabled(),
+            }),
+        }
+    }
+
+    /// Get the format matching a file extension (without the leading dot)
+    ///
+    /// Matching is case-insensitive and does not depend on the enabled features.
+    pub fn from_extension(extension: &str) -> Option<Format> {
+        match extension.to_ascii_lowercase().as_str() {
+            "json" => Some(Format::Json),
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+
+    /// Get the format of a file based on its extension
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Format> {
+        path.as_ref()
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .and_then(Format::from_extension)
+    }
+
+    /// Whether the feature backing this format is enabled
+    pub fn is_enabled(self) -> bool {
+        match self {
+            Format::Json => cfg!(feature = "json"),
+            Format::Yaml => cfg!(feature = "yaml"),
+            Format::Toml => cfg!(feature = "toml"),
+        }
+    }
+
+    /// Parse `input` into `T`
+    #[cfg_attr(
+        not(any(feature = "json", feature = "yaml", feature = "toml")),
+        allow(unused_variables)
+    )]
+    pub fn parse<T>(self, input: &str) -> crate::Result<T>
+    where
+        T: DeserializeOwned,
+    {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => {
+                let mut deserializer = serde_json::Deserializer::from_str(input);
+
+                let value = crate::fs::deserialize(&mut deserializer, Error::JSON)?;
+                deserializer.end()?;
+
+                Ok(value)
+            }
+            #[cfg(feature = "yaml")]
+            Format::Yaml => {
+                crate::fs::deserialize(serde_yaml::Deserializer::from_str(input), |err| {
+                    crate::fs::yaml_error(err, input)
+                })
+            }
+            #[cfg(feature = "toml")]
+            Format::Toml => {
+                crate::fs::deserialize(&mut toml::Deserializer::new(input), Error::TOML)
+            }
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::FormatDisabled(self)),
+        }
+    }
+
+    /// Serialize `value` into a string, pretty-printed where the format allows it
+    #[cfg_attr(
+        not(any(feature = "json", feature = "yaml", feature = "toml")),
+        allow(unused_variables)
+    )]
+    pub fn serialize<T>(self, value: &T) -> crate::Result<String>
+    where
+        T: Serialize + ?Sized,
+    {
+        match self {
+            #[cfg(feature = "json")]
+            Format::Json => serde_json::to_string_pretty(value).map_err(Error::JSON),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_string(value).map_err(Error::YAML),
+            #[cfg(feature = "toml")]
+            Format::Toml => toml::to_string(value).map_err(Error::TomlWrite),
+            #[allow(unreachable_patterns)]
+            _ => Err(Error::FormatDisabled(self)),
+        }
+    }
+}
+
+/// Detect the format of `path` from its extension (`json`, `yaml`/`yml`, `toml`)
+///
+/// Returns `None` for an unknown extension or a format whose feature is disabled.
+/// `json5`/`jsonc` files are not detected as JSON since they aren't valid JSON
+/// in general (comments, trailing commas, ...).
+pub fn detect_format(path: impl AsRef<Path>) -> Option<Format> {
+    Format::from_path(path).filter(|format| format.is_enabled())
+}
+
+/// Load the file at `path` into `T`, the format is detected from its extension
+pub fn load_auto<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let format = Format::for_path(path)?;
+    let content = crate::fs::read_config(path)?;
+
+    format.parse(&content)
+}
+
+/// Check that the file at `path` is syntactically valid `format`,
+/// without deserializing it into a specific type.
+///
+/// The file is read like [`crate::fs::read_config`] does for `load` (a UTF-8 BOM
+/// is stripped and an empty file fails with `Error::EmptyConfig`). Parsing errors
+/// are wrapped in `Error::InFile` and report the line/column where the problem was found.
+#[cfg_attr(
+    not(any(feature = "json", feature = "yaml", feature = "toml")),
+    allow(unused_variables)
+)]
+pub fn validate_syntax(path: impl AsRef<Path>, format: Format) -> crate::Result<()> {
+    let path = path.as_ref();
+    let content = crate::fs::read_config(path)?;
+
+    match format {
+        #[cfg(feature = "json")]
+        Format::Json => serde_json::from_str::<serde_json::Value>(&content)
+            .map(drop)
+            .map_err(|err| Error::JSON(err).in_file(path)),
+        #[cfg(feature = "yaml")]
+        Format::Yaml => serde_yaml::from_str::<serde_yaml::Value>(&content)
+            .map(drop)
+            .map_err(|err| Error::YAML(err).in_file(path)),
+        #[cfg(feature = "toml")]
+        Format::Toml => toml::from_str::<toml::Value>(&content)
+            .map(drop)
+            .map_err(|err| Error::TOML(err).in_file(path)),
+        #[allow(unreachable_patterns)]
+        _ => Err(Error::FormatDisabled(format)),
+    }
+}
+
+/// Write `value` into the file at `path`, the format is detected from its extension
+pub fn write_auto<T>(value: &T, path: impl AsRef<Path>) -> crate::Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    let path = path.as_ref();
+    let format = Format::for_path(path)?;
+
+    std::fs::write(path, format.serialize(value)?)?;
+
+    Ok(())
+}
+
+/// Check that the config at `path` survives being written back: it's loaded
+/// into `T`, serialized in memory, loaded again and compared with the first load
+///
+/// Returns `false` when data would be lost (e.g. a field which isn't serialized,
+/// or a float written with less precision), a diagnostic for a `config doctor`
+/// command. The format is detected from the extension, nothing is written.
+pub fn verify_roundtrip<T>(path: impl AsRef<Path>) -> crate::Result<bool>
+where
+    T: DeserializeOwned + Serialize + PartialEq,
+{
+    let path = path.as_ref();
+    let format = Format::for_path(path)?;
+
+    let loaded: T = format.parse(&crate::fs::read_config(path)?)?;
+    let reloaded: T = format.parse(&format.serialize(&loaded)?)?;
+
+    Ok(loaded == reloaded)
+}
+
+/// Write `value` into the file at `path` omitting every `None`/`null` field,
+/// consistently across formats. The format is detected from the extension.
+pub fn write_skip_none<T>(value: &T, path: impl AsRef<Path>) -> crate::Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    let path = path.as_ref();
+    let format = Format::for_path(path)?;
+
+    let mut value = serde_json::to_value(value)?;
+    crate::value::prune_nulls(&mut value);
+
+    std::fs::write(path, crate::value::serialize_value(&value, format)?)?;
+
+    Ok(())
+}
+
+/// Write `value` into the file at `path` rounding every float to `decimals`
+/// decimal places (e.g. `0.1 + 0.2` is written as `0.3` rather than
+/// `0.30000000000000004`), the format is detected from its extension.
+///
+/// **Precision is lost:** the values loaded back are the rounded ones.
+/// Floats are otherwise always written using the shortest representation
+/// that round-trips. `None` fields are omitted, as TOML cannot hold them.
+pub fn write_rounded<T>(value: &T, path: impl AsRef<Path>, decimals: u32) -> crate::Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    let path = path.as_ref();
+    let format = Format::for_path(path)?;
+
+    let mut value = serde_json::to_value(value)?;
+    crate::value::round_floats(&mut value, decimals);
+    crate::value::prune_nulls(&mut value);
+
+    std::fs::write(path, crate::value::serialize_value(&value, format)?)?;
+
+    Ok(())
+}
+
+/// Write `value` into the file at `path` so that the same value always
+/// produces byte-identical output, the format is detected from its extension.
+///
+/// The following is normalized:
+/// - map keys are sorted (so `HashMap` iteration order doesn't matter)
+/// - line endings are always `\n`
+/// - the file always ends with exactly one `\n`
+/// - floats use the shortest representation that round-trips
+///
+/// Nothing environment-dependent (e.g. a timestamp) is ever written.
+pub fn write_reproducible<T>(value: &T, path: impl AsRef<Path>) -> crate::Result<()>
+where
+    T: Serialize + ?Sized,
+{
+    let path = path.as_ref();
+    let format = Format::for_path(path)?;
+
+    let mut value = serde_json::to_value(value)?;
+    crate::value::sort_keys(&mut value);
+    let content = crate::value::serialize_value(&value, format)?.replace("\r\n", "\n");
+
+    std::fs::write(path, format!("{}\n", content.trim_end_matches('\n')))?;
+
+    Ok(())
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+            Format::Toml => "toml",
+        };
+
+        f.write_str(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(
+            Format::from_path(Path::new("config.json")),
+            Some(Format::Json)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("config.YML")),
+            Some(Format::Yaml)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("config.yaml")),
+            Some(Format::Yaml)
+        );
+        assert_eq!(
+            Format::from_path(Path::new("config.toml")),
+            Some(Format::Toml)
+        );
+        assert_eq!(Format::from_path(Path::new("config.xyz")), None);
+        assert_eq!(Format::from_path(Path::new(".myapprc")), None);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        let detect = |path| detect_format(Path::new(path));
+
+        assert_eq!(
+            detect("config.json"),
+            cfg!(feature = "json").then_some(Format::Json)
+        );
+        assert_eq!(
+            detect("config.yaml"),
+            cfg!(feature = "yaml").then_some(Format::Yaml)
+        );
+        assert_eq!(
+            detect("config.yml"),
+            cfg!(feature = "yaml").then_some(Format::Yaml)
+        );
+        assert_eq!(
+            detect("config.toml"),
+            cfg!(feature = "toml").then_some(Format::Toml)
+        );
+        assert_eq!(
+            detect("config.YML"),
+            cfg!(feature = "yaml").then_some(Format::Yaml)
+        );
+        assert_eq!(detect("config.jsonc"), None);
+        assert_eq!(detect("config.json5"), None);
+        assert_eq!(detect("config.ini"), None);
+        assert_eq!(detect("config"), None);
+    }
+
+    #[test]
+    fn test_unknown_extension() {
+        let path = Path::new("config.xyz");
+        let err = load_auto::<serde_json::Value>(path).unwrap_err();
+
+        assert!(matches!(
+            &err,
+            Error::UnsupportedExtension { extension, supported }
+                if extension == "xyz" && supported == &Format::enabled()
+        ));
+
+        let message = err.to_string();
+        assert!(message.contains("xyz"), "{message}");
+        for format in Format::enabled() {
+            assert!(message.contains(&format.to_string()), "{message}");
+        }
+
+        assert!(write_auto(&serde_json::json!({}), path).is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_write_reproducible() {
+        use std::collections::HashMap;
+
+        #[derive(Serialize)]
+        struct Reproducible {
+            ratio: f64,
+            entries: HashMap<String, u32>,
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let first = dir.path().join("first.json");
+        let second = dir.path().join("second.json");
+
+        // each `HashMap` is randomly seeded, so the iteration order differs
+        let entries: HashMap<_, _> = (0..50).map(|idx| (format!("key-{idx}"), idx)).collect();
+        let reversed: HashMap<_, _> = (0..50)
+            .rev()
+            .map(|idx| (format!("key-{idx}"), idx))
+            .collect();
+
+        write_reproducible(
+            &Reproducible {
+                ratio: 0.1,
+                entries,
+            },
+            &first,
+        )
+        .unwrap();
+        write_reproducible(
+            &Reproducible {
+                ratio: 0.1,
+                entries: reversed,
+            },
+            &second,
+        )
+        .unwrap();
+
+        let first = std::fs::read(first).unwrap();
+        assert_eq!(first, std::fs::read(second).unwrap());
+        assert!(first.ends_with(b"}\n"));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_write_reproducible_toml() {
+        use std::collections::HashMap;
+
+        #[derive(Serialize)]
+        struct Reproducible {
+            server: Server,
+            name: &'static str,
+            description: Option<String>,
+        }
+
+        #[derive(Serialize)]
+        struct Server {
+            port: u16,
+            tags: HashMap<String, u32>,
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let first = dir.path().join("first.toml");
+        let second = dir.path().join("second.toml");
+
+        let config = |tags: HashMap<String, u32>| Reproducible {
+            server: Server { port: 8080, tags },
+            name: "app",
+            description: None,
+        };
+
+        let tags: HashMap<_, _> = (0..50).map(|idx| (format!("key-{idx}"), idx)).collect();
+        let reversed: HashMap<_, _> = (0..50)
+            .rev()
+            .map(|idx| (format!("key-{idx}"), idx))
+            .collect();
+
+        write_reproducible(&config(tags), &first).unwrap();
+        write_reproducible(&config(reversed), &second).unwrap();
+
+        let content = std::fs::read_to_string(&first).unwrap();
+        assert_eq!(content, std::fs::read_to_string(second).unwrap());
+        assert!(content.starts_with("name = \"app\"\n"), "{content}");
+        assert!(!content.contains("description"), "{content}");
+        assert!(
+            content.ends_with("\n") && !content.ends_with("\n\n"),
+            "{content}"
+        );
+    }
+
+    #[cfg(any(feature = "json", feature = "yaml"))]
+    mod skip_none {
+        use super::*;
+
+        #[derive(Serialize)]
+        struct Optional {
+            name: &'static str,
+            description: Option<String>,
+            nested: Nested,
+        }
+
+        #[derive(Serialize)]
+        struct Nested {
+            token: Option<String>,
+            items: Vec<Option<u32>>,
+        }
+
+        fn write_optional(extension: &str) -> String {
+            let dir = tempdir::TempDir::new("test_config").unwrap();
+            let path = dir.path().join(format!("config.{extension}"));
+
+            let config = Optional {
+                name: "app",
+                description: None,
+                nested: Nested {
+                    token: None,
+                    items: vec![Some(1), None],
+                },
+            };
+
+            write_skip_none(&config, &path).unwrap();
+
+            std::fs::read_to_string(path).unwrap()
+        }
+
+        #[cfg(feature = "json")]
+        #[test]
+        fn test_write_skip_none_json() {
+            let content = write_optional("json");
+
+            assert!(!content.contains("description"), "{content}");
+            assert!(!content.contains("token"), "{content}");
+            assert!(content.contains("\"name\""), "{content}");
+
+            let value: serde_json::Value = serde_json::from_str(&content).unwrap();
+            assert_eq!(value["nested"]["items"], serde_json::json!([1, null]));
+        }
+
+        #[cfg(feature = "yaml")]
+        #[test]
+        fn test_write_skip_none_yaml() {
+            let content = write_optional("yaml");
+
+            assert!(!content.contains("description"), "{content}");
+            assert!(!content.contains("token"), "{content}");
+            assert!(content.contains("name: app"), "{content}");
+        }
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_write_skip_none_toml() {
+        #[derive(Serialize)]
+        struct Optional {
+            nested: Nested,
+            name: &'static str,
+            description: Option<String>,
+            homepage: Option<String>,
+        }
+
+        #[derive(Serialize)]
+        struct Nested {
+            token: Option<String>,
+            secret: Option<String>,
+            port: u16,
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.toml");
+
+        let config = Optional {
+            nested: Nested {
+                token: None,
+                secret: None,
+                port: 8080,
+            },
+            name: "app",
+            description: None,
+            homepage: None,
+        };
+
+        write_skip_none(&config, &path).unwrap();
+        let content = std::fs::read_to_string(path).unwrap();
+
+        for field in ["description", "homepage", "token", "secret"] {
+            assert!(!content.contains(field), "{content}");
+        }
+
+        let value: toml::Value = toml::from_str(&content).unwrap();
+        assert_eq!(value["name"].as_str(), Some("app"));
+        assert_eq!(value["nested"]["port"].as_integer(), Some(8080));
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_load_auto_yml() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let config = crate::test_utils::TestConfig::default();
+
+        for name in ["config.yml", "config.yaml"] {
+            let path = dir.path().join(name);
+
+            write_auto(&config, &path).unwrap();
+            assert_eq!(
+                load_auto::<crate::test_utils::TestConfig>(&path).unwrap(),
+                config
+            );
+        }
+    }
+
+    #[cfg(not(feature = "yaml"))]
+    #[test]
+    fn test_disabled_format() {
+        let path = Path::new("config.yaml");
+
+        let err = load_auto::<serde_json::Value>(path).unwrap_err();
+        assert!(matches!(err, Error::FormatDisabled(Format::Yaml)));
+        assert_eq!(err.to_string(), "yaml support not compiled in");
+
+        assert!(matches!(
+            Format::for_path("config.yml"),
+            Err(Error::FormatDisabled(Format::Yaml))
+        ));
+        assert_eq!(detect_format(path), None);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_write_rounded() {
+        #[derive(Serialize, serde::Deserialize)]
+        struct Ratios {
+            sum: f64,
+            third: f64,
+            count: u64,
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        let ratios = Ratios {
+            sum: 0.1 + 0.2,
+            third: 2.0 / 3.0,
+            count: 12345,
+        };
+
+        write_rounded(&ratios, &path, 3).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("\"sum\": 0.3"), "{content}");
+        assert!(content.contains("\"third\": 0.667"), "{content}");
+        assert!(content.contains("\"count\": 12345"), "{content}");
+
+        let loaded: Ratios = load_auto(&path).unwrap();
+        assert!((loaded.sum - ratios.sum).abs() < 1e-3);
+        assert!((loaded.third - ratios.third).abs() < 1e-3);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_write_rounded_toml() {
+        #[derive(Serialize, serde::Deserialize)]
+        struct Ratios {
+            limits: Limits,
+            sum: f64,
+            label: Option<String>,
+        }
+
+        #[derive(Serialize, serde::Deserialize)]
+        struct Limits {
+            third: f64,
+            max: Option<f64>,
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.toml");
+        let ratios = Ratios {
+            limits: Limits {
+                third: 2.0 / 3.0,
+                max: None,
+            },
+            sum: 0.1 + 0.2,
+            label: None,
+        };
+
+        write_rounded(&ratios, &path, 3).unwrap();
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("sum = 0.3\n"), "{content}");
+        assert!(content.contains("third = 0.667\n"), "{content}");
+        assert!(
+            !content.contains("label") && !content.contains("max"),
+            "{content}"
+        );
+
+        let loaded: Ratios = load_auto(&path).unwrap();
+        assert!(loaded.label.is_none() && loaded.limits.max.is_none());
+        assert!((loaded.limits.third - ratios.limits.third).abs() < 1e-3);
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_validate_syntax() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+
+        std::fs::write(&path, r#"{ "any": ["shape", 1] }"#).unwrap();
+        assert!(validate_syntax(&path, Format::Json).is_ok());
+
+        std::fs::write(&path, "{\n  \"any\": [1,\n}").unwrap();
+        let err = validate_syntax(&path, Format::Json).unwrap_err();
+
+        assert!(matches!(&err, Error::InFile { path: file, .. } if file == &path));
+        assert_eq!(err.json_error().unwrap().line(), 3);
+        assert!(err.to_string().contains("line 3"), "{err}");
+
+        // same as `load`
+        std::fs::write(&path, "\u{feff}{ \"any\": 1 }").unwrap();
+        assert!(validate_syntax(&path, Format::Json).is_ok());
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_validate_syntax_toml() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.toml");
+
+        std::fs::write(&path, "any = [\"shape\", \"other\"]\n").unwrap();
+        assert!(validate_syntax(&path, Format::Toml).is_ok());
+
+        std::fs::write(&path, "name = \"app\"\nany = \n").unwrap();
+        let err = validate_syntax(&path, Format::Toml).unwrap_err();
+
+        assert!(matches!(&err, Error::InFile { path: file, source }
+            if file == &path && matches!(**source, Error::TOML(_))));
+        assert!(err.to_string().contains("line 2"), "{err}");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_load_write_auto() {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        let config = crate::test_utils::TestConfig::default();
+
+        write_auto(&config, &path).unwrap();
+        assert_eq!(
+            load_auto::<crate::test_utils::TestConfig>(&path).unwrap(),
+            config
+        );
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_verify_roundtrip() {
+        #[derive(PartialEq, Serialize, serde::Deserialize)]
+        struct Measurement {
+            #[serde(serialize_with = "two_decimals")]
+            ratio: f64,
+        }
+
+        fn two_decimals<S: serde::Serializer>(
+            value: &f64,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            serializer.serialize_f64((value * 100.0).round() / 100.0)
+        }
+
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+
+        std::fs::write(&path, r#"{ "ratio": 0.25 }"#).unwrap();
+        assert!(verify_roundtrip::<Measurement>(&path).unwrap());
+
+        std::fs::write(&path, r#"{ "ratio": 0.123456 }"#).unwrap();
+        assert!(!verify_roundtrip::<Measurement>(&path).unwrap());
+
+        // the file itself is left untouched
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            r#"{ "ratio": 0.123456 }"#
+        );
+    }
+}
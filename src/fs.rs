@@ -6,24 +6,537 @@
 
 /// Generic trait
 pub trait File {
-    fn load(path: &Path) -> crate::Result<Self>
+    fn load(path: impl AsRef<Path>) -> crate::Result<Self>
     where
         Self: Sized;
 
-    fn write(&self, path: &Path) -> crate::Result<()>;
+    /// Write `Self` into specified file
+    ///
+    /// This is the method to implement, unlike [`File::write`] it can be
+    /// called on a `dyn File`.
+    fn write_path(&self, path: &Path) -> crate::Result<()>;
+
+    /// Write `Self` into specified file, see [`File::write_path`]
+    fn write(&self, path: impl AsRef<Path>) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        self.write_path(path.as_ref())
+    }
+
+    /// Load file content into `Self`, falling back to the backup file
+    /// (see [`backup_path`]) when the main file cannot be parsed.
+    ///
+    /// The returned `bool` is `true` when the value was recovered from the backup.
+    /// Filesystem errors (e.g. a missing file) are returned as-is since there is
+    /// nothing to recover from.
+    fn load_with_recovery(path: impl AsRef<Path>) -> crate::Result<(Self, bool)>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        match Self::load(path) {
+            Ok(config) => Ok((config, false)),
+            Err(err @ Error::FileSystem(_)) => Err(err),
+            Err(err) => match Self::load(backup_path(path)) {
+                Ok(config) => Ok((config, true)),
+                // report the original failure, not the backup one
+                Err(_) => Err(err),
+            },
+        }
+    }
+
+    /// Write `Self` into specified file keeping the permissions
+    /// (and, on Unix, the owner and group) of the file being replaced.
+    ///
+    /// Restoring the ownership is best-effort: it's skipped when the
+    /// current user is not allowed to change it.
+    fn write_preserving_metadata(&self, path: impl AsRef<Path>) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let previous = std::fs::metadata(path).ok();
+
+        self.write(path)?;
+
+        match previous {
+            Some(previous) => restore_metadata(path, &previous),
+            None => Ok(()),
+        }
+    }
+
+    /// Write `Self` into specified file using `line_ending` for its newlines,
+    /// e.g. [`LineEnding::CrLf`] to match the other files of a Windows user
+    ///
+    /// [`File::write`] always uses `\n`. A `\r\n` config is written to a temporary
+    /// file next to `path` first, then renamed over it once converted, so `path`
+    /// never holds a partially converted config.
+    fn write_with_line_ending(
+        &self,
+        path: impl AsRef<Path>,
+        line_ending: LineEnding,
+    ) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        if line_ending.resolve() != LineEnding::CrLf {
+            return self.write(path);
+        }
+
+        let temp = temp_path(path, "crlf");
+        let written = self.write(&temp).and_then(|()| {
+            let content = std::fs::read_to_string(&temp)?;
+            std::fs::write(&temp, content.replace("\r\n", "\n").replace('\n', "\r\n"))?;
+
+            // keep the permissions of the replaced config
+            if let Ok(metadata) = std::fs::metadata(path) {
+                std::fs::set_permissions(&temp, metadata.permissions())?;
+            }
+
+            Ok(std::fs::rename(&temp, path)?)
+        });
+
+        if written.is_err() {
+            let _ = std::fs::remove_file(&temp);
+        }
+
+        written
+    }
+
+    /// Write `Self` into specified file and return where it actually landed,
+    /// i.e. the canonical path with the symlinks resolved (e.g. for logging)
+    fn write_reporting(&self, path: impl AsRef<Path>) -> crate::Result<PathBuf>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        self.write(path)?;
+
+        Ok(path.canonicalize()?)
+    }
+
+    /// Write `Self` into specified file and wait for the data to reach the disk,
+    /// e.g. for a config which must survive an immediate power loss.
+    ///
+    /// This is much slower than [`File::write`]: each call flushes the file
+    /// (and its directory on Unix) through the OS caches with `fsync`.
+    fn write_durable(&self, path: impl AsRef<Path>) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        self.write(path)?;
+        sync_to_disk(path)
+    }
+
+    /// Write `Self` into specified file, first appending its previous content
+    /// (if any) to `journal` so that it can be restored with [`undo_last`]
+    ///
+    /// Each journal entry is timestamped, the journal grows on every write.
+    fn write_journaled(
+        &self,
+        path: impl AsRef<Path>,
+        journal: impl AsRef<Path>,
+    ) -> crate::Result<()>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+
+        match std::fs::read(path) {
+            Ok(previous) => append_journal(journal.as_ref(), &previous)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(Error::FileSystem(err)),
+        }
+
+        self.write(path)
+    }
+
+    /// Write `Self` into specified file unless it would change one of the
+    /// `immutable_fields` (dotted paths, e.g. `machine_id`) of the file on disk,
+    /// failing with `Error::ImmutableField` naming the first one changed
+    ///
+    /// A field can still be set once: only the ones present (and not `null`)
+    /// in the existing file are checked, and nothing is when the file is missing.
+    /// The existing file is read as-is, its format detected from the extension.
+    fn write_guarded(&self, path: impl AsRef<Path>, immutable_fields: &[&str]) -> crate::Result<()>
+    where
+        Self: serde::Serialize + Sized,
+    {
+        let path = path.as_ref();
+
+        if path.exists() {
+            // not `Self::load`, which would fill the missing fields with their default
+            let current = crate::value::load_value(path)?;
+            let updated = serde_json::to_value(self)?;
+
+            for field in immutable_fields {
+                match crate::value::get_path(&current, field) {
+                    None | Some(serde_json::Value::Null) => {}
+                    Some(value) => {
+                        if crate::value::get_path(&updated, field) != Some(value) {
+                            return Err(Error::ImmutableField(field.to_string()));
+                        }
+                    }
+                }
+            }
+        }
+
+        self.write(path)
+    }
+
+    /// Write `Self` into a temporary file next to `path`, leaving `path` untouched
+    /// until [`StagedWrite::commit`] renames it into place
+    ///
+    /// Meant for a "stage then commit" workflow, where the staged file is
+    /// validated (e.g. by an external tool) first. The temporary file is
+    /// deleted by [`StagedWrite::discard`], or when the handle is dropped.
+    ///
+    /// Each call stages into its own temporary file (see [`StagedWrite::temp_path`]),
+    /// so concurrent writers never share one.
+    fn stage_write(&self, path: impl AsRef<Path>) -> crate::Result<StagedWrite>
+    where
+        Self: Sized,
+    {
+        let target = path.as_ref().to_path_buf();
+        let temp = temp_path(&target, "staged");
+
+        // never take over an existing file
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temp)?;
+
+        let staged = StagedWrite {
+            temp,
+            target,
+            done: false,
+        };
+        self.write(&staged.temp)?;
+
+        Ok(staged)
+    }
+
+    /// Load file content into `Self` and keep a shared lock on the file
+    /// until the returned [`ConfigLock`] is dropped, preventing writers
+    /// which take an exclusive lock while the config is in use.
+    ///
+    /// Blocks until any exclusive lock held on the file is released.
+    /// See [`ConfigLock`] for the semantics on each platform.
+    fn load_with_guard(path: impl AsRef<Path>) -> crate::Result<(Self, ConfigLock)>
+    where
+        Self: Sized,
+    {
+        let path = path.as_ref();
+        let lock = ConfigLock::shared(path)?;
+
+        Ok((Self::load(path)?, lock))
+    }
+
+    /// Render `Self` in `format` without writing it anywhere,
+    /// e.g. for a `config show` command
+    ///
+    /// Human formats are pretty-printed, see [`crate::format::Format::serialize`].
+    fn to_pretty_string(&self, format: crate::format::Format) -> crate::Result<String>
+    where
+        Self: serde::Serialize + Sized,
+    {
+        format.serialize(self)
+    }
+
+    /// Export `Self` as `PREFIX_NESTED_KEY='value'` lines, see [`crate::shell::to_shell_env`]
+    fn to_shell_env(&self, prefix: &str) -> crate::Result<String>
+    where
+        Self: serde::Serialize + Sized,
+    {
+        crate::shell::to_shell_env(self, prefix)
+    }
+
+    /// Same as [`File::to_pretty_string`] but the values at `secret_fields`
+    /// (dotted paths, e.g. `auth.token`) are replaced with `"***"`
+    ///
+    /// Meant to log or display a config: never write the result back to disk.
+    fn to_redacted_string(
+        &self,
+        format: crate::format::Format,
+        secret_fields: &[&str],
+    ) -> crate::Result<String>
+    where
+        Self: serde::Serialize + Sized,
+    {
+        let mut config = serde_json::to_value(self)?;
+        crate::value::redact(&mut config, secret_fields);
+
+        crate::value::serialize_value(&config, format)
+    }
+}
+
+/// Newlines used by [`File::write_with_line_ending`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    /// `\n`
+    #[default]
+    Lf,
+    /// `\r\n`
+    CrLf,
+    /// `\r\n` on Windows, `\n` elsewhere
+    Native,
+}
+
+impl LineEnding {
+    /// Resolve [`LineEnding::Native`] for the current platform
+    pub fn resolve(self) -> LineEnding {
+        match self {
+            LineEnding::Native if cfg!(windows) => LineEnding::CrLf,
+            LineEnding::Native => LineEnding::Lf,
+            line_ending => line_ending,
+        }
+    }
+}
+
+/// A config written by [`File::stage_write`], waiting to be committed
+#[derive(Debug)]
+pub struct StagedWrite {
+    temp: PathBuf,
+    target: PathBuf,
+    done: bool,
+}
+
+impl StagedWrite {
+    /// Path of the temporary file holding the staged config
+    pub fn temp_path(&self) -> &Path {
+        &self.temp
+    }
+
+    /// Path the staged config is committed to
+    pub fn target(&self) -> &Path {
+        &self.target
+    }
+
+    /// Atomically replace the target with the staged config
+    pub fn commit(mut self) -> crate::Result<()> {
+        std::fs::rename(&self.temp, &self.target)?;
+        self.done = true;
+
+        Ok(())
+    }
+
+    /// Delete the staged config, the target is left untouched
+    pub fn discard(mut self) -> crate::Result<()> {
+        self.done = true;
+
+        Ok(std::fs::remove_file(&self.temp)?)
+    }
+}
+
+impl Drop for StagedWrite {
+    fn drop(&mut self) {
+        if !self.done {
+            let _ = std::fs::remove_file(&self.temp);
+        }
+    }
+}
+
+/// A shared lock held on a config file, released on drop
+///
+/// On Unix this is an advisory `flock`: it only excludes the processes
+/// which lock the file as well, plain reads and writes are not prevented.
+/// On Windows the lock is mandatory and other handles cannot write
+/// the file while it's held.
+#[derive(Debug)]
+pub struct ConfigLock {
+    _file: std::fs::File,
+}
+
+impl ConfigLock {
+    /// Wait for a shared lock on the file at `path`
+    fn shared(path: &Path) -> crate::Result<Self> {
+        let file = std::fs::File::open(path)?;
+        file.lock_shared()?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Header of a journal entry (`#journal {unix timestamp} {length}`)
+const JOURNAL_HEADER: &str = "#journal";
+
+/// Append `content` to `journal` as a new entry
+fn append_journal(journal: &Path, content: &[u8]) -> crate::Result<()> {
+    use std::io::Write;
+
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(journal)?;
+
+    let mut entry = format!("{JOURNAL_HEADER} {timestamp} {}\n", content.len()).into_bytes();
+    entry.extend_from_slice(content);
+    entry.push(b'\n');
+
+    file.write_all(&entry)?;
+
+    Ok(())
+}
+
+/// Restore `path` to the most recent content recorded in `journal` by
+/// [`File::write_journaled`], removing that entry from the journal.
+///
+/// Returns `false` when the journal is empty (or missing).
+pub fn undo_last(path: impl AsRef<Path>, journal: impl AsRef<Path>) -> crate::Result<bool> {
+    let journal = journal.as_ref();
+
+    let bytes = match std::fs::read(journal) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(err) => return Err(Error::FileSystem(err)),
+    };
+
+    // (offset of the entry, its content)
+    let mut last = None;
+    let mut offset = 0;
+
+    while offset < bytes.len() {
+        let corrupted = || Error::InvalidConfig("corrupted journal");
+
+        let header_end = bytes[offset..]
+            .iter()
+            .position(|byte| *byte == b'\n')
+            .map(|idx| offset + idx)
+            .ok_or_else(corrupted)?;
+
+        let header = std::str::from_utf8(&bytes[offset..header_end]).map_err(|_| corrupted())?;
+        let length: usize = match header.split(' ').collect::<Vec<_>>()[..] {
+            [JOURNAL_HEADER, _, length] => length.parse().map_err(|_| corrupted())?,
+            _ => return Err(corrupted()),
+        };
+
+        let start = header_end + 1;
+        let end = start.checked_add(length).ok_or_else(corrupted)?;
+        if bytes.get(end) != Some(&b'\n') {
+            return Err(corrupted());
+        }
+
+        last = Some((offset, &bytes[start..end]));
+        offset = end + 1;
+    }
+
+    let Some((entry_offset, content)) = last else {
+        return Ok(false);
+    };
+
+    std::fs::write(path, content)?;
+    std::fs::OpenOptions::new()
+        .write(true)
+        .open(journal)?
+        .set_len(entry_offset as u64)?;
+
+    Ok(true)
+}
+
+/// Flush `path` and, on Unix, its parent directory (so that a newly created
+/// entry is persisted too) to disk
+fn sync_to_disk(path: &Path) -> crate::Result<()> {
+    std::fs::File::open(path)?.sync_all()?;
+
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        std::fs::File::open(dir)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Post-load transformations (e.g. normalizing values or resolving relative paths)
+///
+/// `post_load` is a no-op by default, override it to canonicalize the config.
+pub trait PostLoad: File {
+    /// Transform `self` right after it has been loaded
+    fn post_load(&mut self) -> crate::Result<()> {
+        Ok(())
+    }
+
+    /// Load file content into `Self` and run [`PostLoad::post_load`] on it
+    fn load_transformed(path: impl AsRef<Path>) -> crate::Result<Self>
+    where
+        Self: Sized,
+    {
+        let mut config = Self::load(path)?;
+        config.post_load()?;
+
+        Ok(config)
+    }
+}
+
+/// Ignore permission errors, used for best-effort operations
+fn ignore_permission_denied(result: std::io::Result<()>) -> crate::Result<()> {
+    match result {
+        Err(err) if err.kind() == std::io::ErrorKind::PermissionDenied => Ok(()),
+        result => result.map_err(Error::FileSystem),
+    }
+}
+
+#[cfg(unix)]
+fn restore_metadata(path: &Path, previous: &std::fs::Metadata) -> crate::Result<()> {
+    use std::os::unix::fs::{chown, MetadataExt, PermissionsExt};
+
+    let current = std::fs::metadata(path)?;
+
+    if (current.uid(), current.gid()) != (previous.uid(), previous.gid()) {
+        ignore_permission_denied(chown(path, Some(previous.uid()), Some(previous.gid())))?;
+    }
+
+    if current.mode() != previous.mode() {
+        let permissions = std::fs::Permissions::from_mode(previous.mode());
+        ignore_permission_denied(std::fs::set_permissions(path, permissions))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn restore_metadata(path: &Path, previous: &std::fs::Metadata) -> crate::Result<()> {
+    ignore_permission_denied(std::fs::set_permissions(path, previous.permissions()))
+}
+
+/// Get the path of the backup file for `path` (`{path}.bak`)
+pub fn backup_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut backup = path.as_ref().as_os_str().to_os_string();
+    backup.push(".bak");
+
+    PathBuf::from(backup)
 }
 
-#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+#[cfg(any(
+    feature = "json",
+    feature = "toml",
+    feature = "yaml",
+    feature = "hjson"
+))]
 use serde::de::DeserializeOwned;
 
-#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+#[cfg(any(
+    feature = "json",
+    feature = "toml",
+    feature = "yaml",
+    feature = "hjson"
+))]
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 #[cfg(feature = "toml")]
 use std::io::Write;
 
-#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
 use crate::error::Error;
 
 #[cfg(feature = "json")]
@@ -32,146 +545,1657 @@ where
     Self: DeserializeOwned + serde::Serialize,
 {
     /// Load file content into `Self`
-    fn load(path: &Path) -> crate::Result<Self> {
-        let file = fs::File::open(path)?;
+    fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = read_config(path)?;
+        let mut deserializer = serde_json::Deserializer::from_str(&file);
 
-        serde_json::from_reader(file).map_err(Error::JSON)
+        let config = deserialize(&mut deserializer, Error::JSON)?;
+        deserializer.end()?;
+
+        Ok(config)
     }
 
     /// Write `Self` into specified file
-    fn write(&self, path: &Path) -> crate::Result<()> {
+    fn write(&self, path: impl AsRef<Path>) -> crate::Result<()> {
         let file = fs::File::create(path)?;
 
         serde_json::to_writer_pretty(file, self).map_err(Error::JSON)
     }
+
+    /// Write `Self` into specified file calling `on_bytes` with the total
+    /// number of bytes written so far (once per buffered chunk)
+    fn write_with_progress<F>(&self, path: impl AsRef<Path>, on_bytes: F) -> crate::Result<()>
+    where
+        F: FnMut(usize),
+    {
+        let file = fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(ProgressWriter::new(file, on_bytes));
+
+        serde_json::to_writer_pretty(&mut writer, self)?;
+        std::io::Write::flush(&mut writer)?;
+
+        Ok(())
+    }
 }
 
-#[cfg(feature = "yaml")]
-pub trait YAMLFile
+/// Reports the total number of bytes written to the inner writer
+#[cfg(any(feature = "json", feature = "yaml"))]
+struct ProgressWriter<W, F> {
+    inner: W,
+    written: usize,
+    on_bytes: F,
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+impl<W, F> ProgressWriter<W, F> {
+    fn new(inner: W, on_bytes: F) -> Self {
+        Self {
+            inner,
+            written: 0,
+            on_bytes,
+        }
+    }
+}
+
+#[cfg(any(feature = "json", feature = "yaml"))]
+impl<W, F> std::io::Write for ProgressWriter<W, F>
+where
+    W: std::io::Write,
+    F: FnMut(usize),
+{
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+
+        self.written += written;
+        (self.on_bytes)(self.written);
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// [HJSON](https://hjson.github.io/) config files (comments, unquoted keys, ...)
+///
+/// Files are written back as standard (pretty) JSON, which is valid HJSON.
+#[cfg(feature = "hjson")]
+pub trait HjsonFile
 where
     Self: DeserializeOwned + serde::Serialize,
 {
     /// Load file content into `Self`
-    fn load(path: &Path) -> crate::Result<Self> {
-        let file = fs::File::open(path)?;
+    fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = read_config(path)?;
 
-        serde_yaml::from_reader(file).map_err(Error::YAML)
+        deser_hjson::from_str(&file).map_err(Error::HJSON)
     }
 
-    /// Write `Self` into specified file
-    fn write(&self, path: &Path) -> crate::Result<()> {
+    /// Write `Self` into specified file as JSON
+    fn write(&self, path: impl AsRef<Path>) -> crate::Result<()> {
         let file = fs::File::create(path)?;
 
-        serde_yaml::to_writer(file, self).map_err(Error::YAML)
+        serde_json::to_writer_pretty(file, self).map_err(Error::JSON)
     }
 }
 
-#[cfg(feature = "toml")]
-pub trait TOMLFile
+/// Load every config file of `dir` into a map keyed by file stem
+/// (e.g. `entities/alice.json` is loaded as `"alice"`).
+///
+/// Only the files whose format is detected by [`crate::format::detect_format`]
+/// are loaded, a malformed file fails with `Error::InFile`.
+/// A missing directory results in an empty map.
+pub fn load_dir<T>(dir: impl AsRef<Path>) -> crate::Result<std::collections::HashMap<String, T>>
 where
-    Self: DeserializeOwned + serde::Serialize,
+    T: serde::de::DeserializeOwned,
 {
-    /// Load file content into `Self`
-    fn load(path: &Path) -> crate::Result<Self> {
-        let file = fs::read_to_string(path)?;
+    let mut configs = std::collections::HashMap::new();
 
-        toml::from_str(&file).map_err(Error::TOML)
-    }
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(configs),
+        Err(err) => return Err(Error::FileSystem(err)),
+    };
 
-    /// Write `Self` into specified file
-    fn write(&self, path: &Path) -> crate::Result<()> {
-        let mut file = fs::File::create(path)?;
-        let str = toml::ser::to_string(&self)?;
+    for entry in entries {
+        let path = entry?.path();
 
-        file.write_all(&str.as_bytes())?;
+        if !path.is_file() || crate::format::detect_format(&path).is_none() {
+            continue;
+        }
 
-        Ok(())
+        let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let config = crate::format::load_auto(&path).map_err(|err| err.in_file(&path))?;
+        configs.insert(name.to_string(), config);
     }
+
+    Ok(configs)
 }
 
-mod test_utils {
-    use serde::{Deserialize, Serialize};
+/// Maximum number of threads used by [`load_many`]
+const MAX_LOAD_THREADS: usize = 8;
 
-    #[derive(Debug, PartialEq, Serialize, Deserialize)]
-    pub struct TestConfig {
-        pub foo: String,
-        pub bar: bool,
-        pub baz: u32,
-    }
-
-    impl Default for TestConfig {
-        fn default() -> Self {
-            Self {
-                foo: "foo".to_string(),
-                bar: true,
-                baz: 42,
-            }
+/// Load several config files concurrently on a small pool of threads.
+///
+/// The results are returned in the same order as `paths`,
+/// a failing file does not prevent the others from being loaded.
+pub fn load_many<T>(paths: Vec<PathBuf>) -> Vec<crate::Result<T>>
+where
+    T: File + Send + 'static,
+{
+    use std::sync::{atomic::AtomicUsize, atomic::Ordering, Mutex};
+
+    let threads = std::thread::available_parallelism()
+        .map(usize::from)
+        .unwrap_or(1)
+        .min(MAX_LOAD_THREADS)
+        .min(paths.len());
+
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new((0..paths.len()).map(|_| None).collect::<Vec<_>>());
+
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let idx = next.fetch_add(1, Ordering::Relaxed);
+                let Some(path) = paths.get(idx) else {
+                    break;
+                };
+
+                let result = T::load(path);
+                results.lock().unwrap_or_else(|err| err.into_inner())[idx] = Some(result);
+            });
         }
-    }
+    });
+
+    results
+        .into_inner()
+        .unwrap_or_else(|err| err.into_inner())
+        .into_iter()
+        .map(|result| result.expect("every path is loaded"))
+        .collect()
 }
 
-#[cfg(feature = "toml")]
-#[cfg(test)]
-mod toml_tests {
-    use super::*;
-    use tempdir::TempDir;
-    use test_utils::TestConfig;
+/// UTF-8 byte order mark, written by some Windows editors
+const UTF8_BOM: &[u8] = b"\xEF\xBB\xBF";
 
-    impl TOMLFile for TestConfig {}
+/// Strip the UTF-8 BOM and the leading blank lines of a config, e.g. before
+/// handing it to the parser of a custom format
+///
+/// The indentation of the first non-blank line is kept since it's significant
+/// in some formats (e.g. YAML). Note that line numbers reported by the parser
+/// won't account for the removed lines.
+pub fn sanitize_input(bytes: &[u8]) -> &[u8] {
+    let bytes = bytes.strip_prefix(UTF8_BOM).unwrap_or(bytes);
 
-    #[test]
-    fn test_file_trait() {
-        let dir = TempDir::new("test_config").unwrap();
-        let config_file = dir.path().join("test-config.json");
-        let config = TestConfig::default();
+    let content = bytes
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .unwrap_or(bytes.len());
 
-        // test write and load
-        config.write(&config_file).unwrap();
-        let loaded_config = TestConfig::load(&config_file).unwrap();
-        assert_eq!(config, loaded_config);
+    match bytes[..content].iter().rposition(|&byte| byte == b'\n') {
+        Some(newline) => &bytes[newline + 1..],
+        None => bytes,
     }
 }
 
-#[cfg(feature = "yaml")]
-#[cfg(test)]
-mod yaml_tests {
-    use super::*;
-    use tempdir::TempDir;
-    use test_utils::TestConfig;
+/// Read the content of a config file, failing with `Error::EmptyConfig`
+/// if the file is empty or contains only whitespace
+///
+/// A UTF-8 BOM is stripped, unlike [`sanitize_input`] the blank lines are
+/// kept so that parsing errors point to the right line.
+pub fn read_config(path: impl AsRef<Path>) -> crate::Result<String> {
+    let path = path.as_ref();
+    let mut content = std::fs::read_to_string(path)?;
 
-    impl YAMLFile for TestConfig {}
+    if content.as_bytes().starts_with(UTF8_BOM) {
+        content.drain(..UTF8_BOM.len());
+    }
 
-    #[test]
-    fn test_file_trait() {
-        let dir = TempDir::new("test_config").unwrap();
-        let config_file = dir.path().join("test-config.json");
-        let config = TestConfig::default();
+    if content.trim().is_empty() {
+        return Err(Error::EmptyConfig(path.to_path_buf()));
+    }
 
-        // test write and load
-        config.write(&config_file).unwrap();
-        let loaded_config = TestConfig::load(&config_file).unwrap();
-        assert_eq!(config, loaded_config);
+    Ok(content)
+}
+
+/// Load the config at `path`, treating an empty (or whitespace-only) file as `T::default()`
+pub fn empty_as_default<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: File + Default,
+{
+    let path = path.as_ref();
+    match read_config(path) {
+        Err(Error::EmptyConfig(_)) => Ok(T::default()),
+        Err(err) => Err(err),
+        Ok(_) => T::load(path),
     }
 }
 
-#[cfg(feature = "json")]
-#[cfg(test)]
-mod json_tests {
-    use super::*;
-    use tempdir::TempDir;
-    use test_utils::TestConfig;
+/// Number of paths handed out by [`temp_path`]
+static TEMP_PATHS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
 
-    impl JSONFile for TestConfig {}
+/// Get a path next to `path` for a temporary file (e.g. `config.json.1234.0.new`),
+/// unique to this call as it holds the process id and a counter
+pub(crate) fn temp_path(path: &Path, suffix: &str) -> PathBuf {
+    let id = TEMP_PATHS.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
 
-    #[test]
-    fn test_file_trait() {
-        let dir = TempDir::new("test_config").unwrap();
-        let config_file = dir.path().join("test-config.json");
-        let config = TestConfig::default();
+    let mut temp = path.as_os_str().to_os_string();
+    temp.push(format!(".{}.{id}.{suffix}", std::process::id()));
 
-        // test write and load
-        config.write(&config_file).unwrap();
-        let loaded_config = TestConfig::load(&config_file).unwrap();
-        assert_eq!(config, loaded_config);
+    PathBuf::from(temp)
+}
+
+/// Deserialize `T` from `deserializer` turning its errors into an [`Error`]
+/// with `to_error`
+///
+/// With the `path-to-error` feature enabled the error is prefixed with the path
+/// of the field which failed (e.g. `servers[0].port`).
+pub(crate) fn deserialize<'de, T, D>(
+    deserializer: D,
+    to_error: impl FnOnce(D::Error) -> Error,
+) -> crate::Result<T>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+{
+    #[cfg(feature = "path-to-error")]
+    return serde_path_to_error::deserialize(deserializer).map_err(|err| {
+        let path = err.path().to_string();
+        let source = to_error(err.into_inner());
+
+        match path.as_str() {
+            // the document itself
+            "." => source,
+            _ => Error::AtPath {
+                path,
+                source: Box::new(source),
+            },
+        }
+    });
+
+    #[cfg(not(feature = "path-to-error"))]
+    T::deserialize(deserializer).map_err(to_error)
+}
+
+/// Deserialize `T` from an existing `deserializer` (e.g. a `serde_json::Deserializer`
+/// reading from a socket), mapping its errors like the `load` of its format
+///
+/// Any deserializer of a supported format can be used, nothing is read from
+/// the filesystem. Trailing data isn't checked, see e.g. `serde_json::Deserializer::end`.
+pub fn from_deserializer<'de, T, D>(deserializer: D) -> crate::Result<T>
+where
+    T: serde::Deserialize<'de>,
+    D: serde::Deserializer<'de>,
+    Error: From<D::Error>,
+{
+    deserialize(deserializer, Error::from)
+}
+
+/// Read the whole file at `path` into memory
+///
+/// Useful together with [`load_borrowed`] to keep the buffer alive.
+pub fn read_bytes(path: impl AsRef<Path>) -> crate::Result<Vec<u8>> {
+    std::fs::read(path).map_err(Error::FileSystem)
+}
+
+/// Deserialize a JSON config borrowing from `bytes`.
+///
+/// `&str` and `#[serde(borrow)] Cow<str>` fields will point into the buffer
+/// instead of being allocated, as long as they don't contain escape sequences.
+#[cfg(feature = "json")]
+pub fn load_borrowed<'a, T>(bytes: &'a [u8]) -> crate::Result<T>
+where
+    T: serde::Deserialize<'a>,
+{
+    serde_json::from_slice(bytes).map_err(Error::JSON)
+}
+
+/// Write a JSON object to `writer` one entry at a time,
+/// without collecting `entries` into a map first.
+///
+/// Keys are written in iteration order and are not deduplicated.
+#[cfg(feature = "json")]
+pub fn write_map_stream<W, I>(mut writer: W, entries: I) -> crate::Result<()>
+where
+    W: std::io::Write,
+    I: IntoIterator<Item = (String, serde_json::Value)>,
+{
+    writer.write_all(b"{")?;
+
+    for (idx, (key, value)) in entries.into_iter().enumerate() {
+        if idx > 0 {
+            writer.write_all(b",")?;
+        }
+
+        serde_json::to_writer(&mut writer, &key)?;
+        writer.write_all(b":")?;
+        serde_json::to_writer(&mut writer, &value)?;
+    }
+
+    writer.write_all(b"}")?;
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Turn every line of `header` into a `#` comment (shared by TOML and YAML)
+#[cfg(any(feature = "toml", feature = "yaml"))]
+fn comment_header(header: &str) -> String {
+    header
+        .lines()
+        .map(|line| match line {
+            "" => "#\n".to_string(),
+            line => format!("# {line}\n"),
+        })
+        .collect()
+}
+
+/// Turn an unknown enum variant written as a YAML tag (e.g. `!Proxy`)
+/// into `Error::UnknownTag`, any other error is returned as `Error::YAML`
+#[cfg(feature = "yaml")]
+pub(crate) fn yaml_error(err: serde_yaml::Error, content: &str) -> Error {
+    let message = err.to_string();
+    let Some((_, variants)) = message.split_once("unknown variant `") else {
+        return Error::YAML(err);
+    };
+
+    // "Proxy`, expected `Server` or `Client` at line ...", every other
+    // chunk is a variant name
+    let mut names = variants.split('`').step_by(2);
+    let tag = format!("!{}", names.next().unwrap_or_default());
+    let Some(line) = content
+        .lines()
+        .position(|line| line.split_whitespace().any(|word| word == tag))
+    else {
+        // not written as a tag (e.g. `{ Proxy: ... }`)
+        return Error::YAML(err);
+    };
+
+    Error::UnknownTag {
+        tag,
+        expected: names.map(|name| format!("!{name}")).collect(),
+        line: line + 1,
+    }
+}
+
+#[cfg(feature = "yaml")]
+pub trait YAMLFile
+where
+    Self: DeserializeOwned + serde::Serialize,
+{
+    /// Load file content into `Self`
+    fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = read_config(path)?;
+
+        deserialize(serde_yaml::Deserializer::from_str(&file), |err| {
+            yaml_error(err, &file)
+        })
+    }
+
+    /// Write `Self` into specified file
+    fn write(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let file = fs::File::create(path)?;
+
+        serde_yaml::to_writer(file, self).map_err(Error::YAML)
+    }
+
+    /// Write `Self` into specified file calling `on_bytes` with the total
+    /// number of bytes written so far (once per buffered chunk)
+    fn write_with_progress<F>(&self, path: impl AsRef<Path>, on_bytes: F) -> crate::Result<()>
+    where
+        F: FnMut(usize),
+    {
+        let file = fs::File::create(path)?;
+        let mut writer = std::io::BufWriter::new(ProgressWriter::new(file, on_bytes));
+
+        serde_yaml::to_writer(&mut writer, self)?;
+        std::io::Write::flush(&mut writer)?;
+
+        Ok(())
+    }
+
+    /// Write `Self` into specified file preceded by `header` as a `#` comment block
+    fn write_with_header(&self, path: impl AsRef<Path>, header: &str) -> crate::Result<()> {
+        let content = serde_yaml::to_string(self)?;
+
+        fs::write(path, comment_header(header) + &content)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "toml")]
+pub trait TOMLFile
+where
+    Self: DeserializeOwned + serde::Serialize,
+{
+    /// Load file content into `Self`
+    fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = read_config(path)?;
+
+        deserialize(&mut toml::Deserializer::new(&file), Error::TOML)
+    }
+
+    /// Same as [`TOMLFile::load`] but also rejects duplicate keys in tables
+    /// deserialized as maps (e.g. `HashMap` fields), which `load` accepts
+    /// keeping the last value.
+    ///
+    /// The document is validated as a whole before being deserialized, the
+    /// error names the duplicate key.
+    fn load_strict_keys(path: impl AsRef<Path>) -> crate::Result<Self> {
+        let file = read_config(path)?;
+        toml::from_str::<toml::Value>(&file)?;
+
+        deserialize(&mut toml::Deserializer::new(&file), Error::TOML)
+    }
+
+    /// Write `Self` into specified file
+    fn write(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let mut file = fs::File::create(path)?;
+        let str = toml::ser::to_string(&self)?;
+
+        file.write_all(str.as_bytes())?;
+
+        Ok(())
+    }
+
+    /// Write `Self` into specified file preceded by `header` as a `#` comment block
+    ///
+    /// JSON has no comment syntax, which is why `JSONFile` has no equivalent.
+    fn write_with_header(&self, path: impl AsRef<Path>, header: &str) -> crate::Result<()> {
+        let content = toml::ser::to_string(&self)?;
+
+        fs::write(path, comment_header(header) + &content)?;
+
+        Ok(())
+    }
+
+    /// Write `Self` into specified file forcing the given style for nested tables
+    #[cfg(feature = "toml-preserve")]
+    fn write_with_style(&self, path: impl AsRef<Path>, style: TableStyle) -> crate::Result<()> {
+        let mut document = toml_edit::ser::to_document(self)?;
+
+        match style {
+            TableStyle::Expanded => expand_tables(document.as_table_mut()),
+            TableStyle::Inline => inline_tables(document.as_table_mut()),
+        }
+
+        fs::write(path, document.to_string())?;
+
+        Ok(())
+    }
+
+    /// Write `Self` into specified file updating only the keys it serializes:
+    /// comments (footer included), formatting and unknown keys of the
+    /// existing document are kept.
+    ///
+    /// Keys `Self` doesn't serialize (e.g. a skipped `None`) are kept as well.
+    /// A missing file is written with [`TOMLFile::write`].
+    #[cfg(feature = "toml-preserve")]
+    fn write_preserving(&self, path: impl AsRef<Path>) -> crate::Result<()> {
+        let path = path.as_ref();
+
+        let mut document = match fs::read_to_string(path) {
+            Ok(content) => content.parse::<toml_edit::DocumentMut>()?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return self.write(path),
+            Err(err) => return Err(Error::FileSystem(err)),
+        };
+
+        let updated = toml_edit::ser::to_document(self)?;
+        update_table(document.as_table_mut(), updated.as_table(), true);
+
+        fs::write(path, document.to_string())?;
+
+        Ok(())
+    }
+}
+
+/// Update `existing` with the keys of `updated`, see [`TOMLFile::write_preserving`]
+///
+/// New tables are written as `[table]` blocks when `expanded` is set.
+#[cfg(feature = "toml-preserve")]
+fn update_table(
+    existing: &mut dyn toml_edit::TableLike,
+    updated: &dyn toml_edit::TableLike,
+    expanded: bool,
+) {
+    use toml_edit::{Item, Value};
+
+    for (key, item) in updated.iter() {
+        if let Some(current) = existing.get_mut(key) {
+            update_item(current, item);
+            continue;
+        }
+
+        let item = match item.clone() {
+            Item::Value(Value::InlineTable(table)) if expanded => {
+                let mut table = table.into_table();
+                expand_tables(&mut table);
+                Item::Table(table)
+            }
+            item => item,
+        };
+
+        existing.insert(key, item);
+    }
+}
+
+#[cfg(feature = "toml-preserve")]
+fn update_item(current: &mut toml_edit::Item, updated: &toml_edit::Item) {
+    if let Some(updated) = updated.as_table_like() {
+        if let Some(current) = current.as_table_mut() {
+            return update_table(current, updated, true);
+        }
+
+        if let Some(current) = current.as_inline_table_mut() {
+            return update_table(current, updated, false);
+        }
+    }
+
+    match (current.as_value_mut(), updated.as_value()) {
+        // keep the whitespace and comments around the value
+        (Some(current), Some(updated)) => {
+            let decor = current.decor().clone();
+            *current = updated.clone();
+            *current.decor_mut() = decor;
+        }
+        _ => *current = updated.clone(),
+    }
+}
+
+/// How nested tables are written by [`TOMLFile::write_with_style`]
+#[cfg(feature = "toml-preserve")]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TableStyle {
+    /// `[table.key]` (and `[[array]]`) blocks
+    #[default]
+    Expanded,
+    /// `key = { ... }` inline tables
+    Inline,
+}
+
+#[cfg(feature = "toml-preserve")]
+fn expand_tables(table: &mut toml_edit::Table) {
+    use toml_edit::Item;
+
+    for (_, item) in table.iter_mut() {
+        let expanded = match std::mem::take(item).into_table() {
+            Ok(table) => Item::Table(table),
+            Err(item) => match item.into_array_of_tables() {
+                Ok(array) => Item::ArrayOfTables(array),
+                Err(item) => item,
+            },
+        };
+
+        *item = expanded;
+
+        match item {
+            Item::Table(table) => expand_tables(table),
+            Item::ArrayOfTables(array) => array.iter_mut().for_each(expand_tables),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(feature = "toml-preserve")]
+fn inline_tables(table: &mut toml_edit::Table) {
+    use toml_edit::{Item, Value};
+
+    for (_, item) in table.iter_mut() {
+        *item = match std::mem::take(item) {
+            Item::Table(table) => Item::Value(Value::InlineTable(table.into_inline_table())),
+            Item::ArrayOfTables(array) => Item::Value(Value::Array(array.into_array())),
+            item => item,
+        };
+    }
+}
+
+#[cfg(feature = "toml")]
+#[cfg(test)]
+mod toml_tests {
+    use super::TOMLFile;
+    use crate::test_utils::TestConfig;
+    use tempdir::TempDir;
+
+    impl TOMLFile for TestConfig {}
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Servers {
+        ports: std::collections::HashMap<String, u16>,
+    }
+
+    impl TOMLFile for Servers {}
+
+    #[test]
+    fn test_load_strict_keys() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.toml");
+        std::fs::write(&config_file, "[ports]\nhttp = 80\nhttp = 8080\n").unwrap();
+
+        // lenient by default, the last value wins
+        assert_eq!(Servers::load(&config_file).unwrap().ports["http"], 8080);
+
+        let err = Servers::load_strict_keys(&config_file).unwrap_err();
+        assert!(matches!(err, crate::error::Error::TOML(_)));
+        assert!(err.to_string().contains("duplicate key: `http`"), "{err}");
+
+        std::fs::write(&config_file, "[ports]\nhttp = 80\n").unwrap();
+        assert_eq!(
+            Servers::load_strict_keys(&config_file).unwrap().ports["http"],
+            80
+        );
+    }
+
+    #[cfg(feature = "path-to-error")]
+    #[test]
+    fn test_load_strict_keys_error_path() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.toml");
+        std::fs::write(&config_file, "[ports]\nhttp = \"80\"\n").unwrap();
+
+        // reported as by `load`
+        for err in [
+            Servers::load(&config_file).unwrap_err(),
+            Servers::load_strict_keys(&config_file).unwrap_err(),
+        ] {
+            assert!(
+                matches!(&err, crate::error::Error::AtPath { path, .. } if path == "ports.http"),
+                "{err:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_trait() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = TestConfig::default();
+
+        // test write and load
+        config.write(&config_file).unwrap();
+        let loaded_config = TestConfig::load(&config_file).unwrap();
+        assert_eq!(config, loaded_config);
+    }
+
+    #[test]
+    fn test_write_with_header() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.toml");
+        let config = TestConfig::default();
+
+        config
+            .write_with_header(&config_file, "Generated by myapp v1.2\n\nedit carefully")
+            .unwrap();
+
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(content.starts_with("# Generated by myapp v1.2\n#\n# edit carefully\n"));
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config);
+    }
+}
+
+#[cfg(feature = "toml-preserve")]
+#[cfg(test)]
+mod toml_style_tests {
+    use super::{TOMLFile, TableStyle};
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+    use tempdir::TempDir;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Settings {
+        enabled: bool,
+    }
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct StyledConfig {
+        name: String,
+        plugins: BTreeMap<String, Settings>,
+    }
+
+    impl TOMLFile for StyledConfig {}
+
+    fn config() -> StyledConfig {
+        StyledConfig {
+            name: "styled".to_string(),
+            plugins: BTreeMap::from([
+                ("git".to_string(), Settings { enabled: true }),
+                ("lsp".to_string(), Settings { enabled: false }),
+            ]),
+        }
+    }
+
+    #[test]
+    fn test_write_expanded() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.toml");
+        let config = config();
+
+        config
+            .write_with_style(&config_file, TableStyle::Expanded)
+            .unwrap();
+
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(content.contains("[plugins.git]"), "{content}");
+        assert!(content.contains("[plugins.lsp]"), "{content}");
+        assert!(!content.contains('{'), "{content}");
+        assert_eq!(StyledConfig::load(&config_file).unwrap(), config);
+    }
+
+    #[test]
+    fn test_write_inline() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.toml");
+        let config = config();
+
+        config
+            .write_with_style(&config_file, TableStyle::Inline)
+            .unwrap();
+
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(!content.contains("[plugins"), "{content}");
+        assert_eq!(StyledConfig::load(&config_file).unwrap(), config);
+    }
+
+    #[test]
+    fn test_write_preserving() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.toml");
+        std::fs::write(
+            &config_file,
+            "# my config\nname = \"old\" # the name\nextra = 1\n\n\
+             [plugins.git]\nenabled = false\ncolor = \"red\"\n\n# footer\n",
+        )
+        .unwrap();
+
+        let config = config();
+        config.write_preserving(&config_file).unwrap();
+
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(content.contains("[plugins.lsp]"), "{content}");
+        assert!(content.starts_with("# my config\n"), "{content}");
+        assert!(
+            content.contains("name = \"styled\" # the name"),
+            "{content}"
+        );
+        assert!(content.contains("extra = 1"), "{content}");
+        assert!(content.contains("color = \"red\""), "{content}");
+        assert!(content.ends_with("# footer\n"), "{content}");
+        assert_eq!(StyledConfig::load(&config_file).unwrap(), config);
+
+        // unknown keys survive another round trip
+        StyledConfig::load(&config_file)
+            .unwrap()
+            .write_preserving(&config_file)
+            .unwrap();
+        assert!(std::fs::read_to_string(&config_file)
+            .unwrap()
+            .contains("extra = 1"));
+    }
+}
+
+#[cfg(feature = "yaml")]
+#[cfg(test)]
+mod yaml_tests {
+    use super::YAMLFile;
+    use crate::error::Error;
+    use crate::test_utils::TestConfig;
+    use tempdir::TempDir;
+
+    impl YAMLFile for TestConfig {}
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    enum Node {
+        Server { port: u16 },
+        Client { url: String },
+    }
+
+    #[derive(Debug, serde::Serialize, serde::Deserialize)]
+    struct Cluster {
+        nodes: Vec<Node>,
+    }
+
+    impl YAMLFile for Cluster {}
+
+    #[test]
+    fn test_unknown_tag() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.yaml");
+
+        std::fs::write(
+            &config_file,
+            "nodes:\n  - !Server\n    port: 80\n  - !Proxy\n    port: 81\n",
+        )
+        .unwrap();
+
+        let err = Cluster::load(&config_file).unwrap_err();
+
+        #[cfg(feature = "path-to-error")]
+        let err = match err {
+            Error::AtPath { path, source } if path == "nodes[1]" => *source,
+            err => panic!("{err:?}"),
+        };
+
+        assert!(
+            matches!(
+                &err,
+                Error::UnknownTag { tag, expected, line: 4 }
+                    if tag == "!Proxy" && expected == &["!Server", "!Client"]
+            ),
+            "{err:?}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "unknown YAML tag `!Proxy` at line 4 (expected one of: !Server, !Client)"
+        );
+
+        std::fs::write(&config_file, "nodes:\n  - !Client\n    url: localhost\n").unwrap();
+        assert_eq!(
+            Cluster::load(&config_file).unwrap().nodes,
+            vec![Node::Client {
+                url: "localhost".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_file_trait() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = TestConfig::default();
+
+        // test write and load
+        config.write(&config_file).unwrap();
+        let loaded_config = TestConfig::load(&config_file).unwrap();
+        assert_eq!(config, loaded_config);
+    }
+
+    #[test]
+    fn test_write_with_header() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.yaml");
+        let config = TestConfig::default();
+
+        config
+            .write_with_header(&config_file, "Generated by myapp v1.2\n\nedit carefully")
+            .unwrap();
+
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(content.starts_with("# Generated by myapp v1.2\n#\n# edit carefully\n"));
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config);
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod json_tests {
+    use super::JSONFile;
+    use crate::error::Error;
+    use crate::test_utils::TestConfig;
+    use tempdir::TempDir;
+
+    impl JSONFile for TestConfig {}
+
+    #[test]
+    fn test_file_trait() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = TestConfig::default();
+
+        // test write and load
+        config.write(&config_file).unwrap();
+        let loaded_config = TestConfig::load(&config_file).unwrap();
+        assert_eq!(config, loaded_config);
+    }
+
+    #[test]
+    fn test_write_with_progress() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Cache {
+            entries: Vec<String>,
+        }
+
+        impl JSONFile for Cache {}
+
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("cache.json");
+        let cache = Cache {
+            entries: (0..10_000).map(|idx| format!("entry-{idx}")).collect(),
+        };
+
+        let mut calls = Vec::new();
+        cache
+            .write_with_progress(&config_file, |total| calls.push(total))
+            .unwrap();
+
+        let size = std::fs::metadata(&config_file).unwrap().len() as usize;
+        assert!(calls.len() > 1);
+        assert!(calls.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(calls.last(), Some(&size));
+    }
+
+    #[test]
+    fn test_empty_file() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        std::fs::write(&config_file, " \n\t").unwrap();
+
+        let err = TestConfig::load(&config_file).unwrap_err();
+        assert!(matches!(&err, Error::EmptyConfig(path) if path == &config_file));
+        assert!(err.to_string().contains("test-config.json"));
+    }
+}
+
+#[cfg(feature = "hjson")]
+#[cfg(test)]
+mod hjson_tests {
+    use super::HjsonFile;
+    use crate::test_utils::TestConfig;
+    use tempdir::TempDir;
+
+    impl HjsonFile for TestConfig {}
+
+    #[test]
+    fn test_load_hjson() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.hjson");
+        std::fs::write(
+            &config_file,
+            "{\n  # the name\n  foo: hjson\n  bar: false\n  // the size\n  baz: 7\n}\n",
+        )
+        .unwrap();
+
+        let config = TestConfig::load(&config_file).unwrap();
+        assert_eq!(config.foo, "hjson");
+        assert!(!config.bar);
+        assert_eq!(config.baz, 7);
+
+        // written back as plain JSON
+        config.write(&config_file).unwrap();
+        let content = std::fs::read_to_string(&config_file).unwrap();
+        assert!(serde_json::from_str::<serde_json::Value>(&content).is_ok());
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config);
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod file_tests {
+    use super::{backup_path, empty_as_default, load_dir, load_many, undo_last, File, PostLoad};
+    use crate::error::Error;
+    use crate::test_utils::TestConfig;
+    use std::fs;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_path_like_arguments() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = TestConfig::default();
+
+        config.write(config_file.clone()).unwrap();
+
+        let path: &str = config_file.to_str().unwrap();
+        assert_eq!(TestConfig::load(path).unwrap(), config);
+        assert_eq!(TestConfig::load(config_file.as_path()).unwrap(), config);
+
+        // still usable as a trait object
+        let other = TestConfig {
+            baz: 7,
+            ..TestConfig::default()
+        };
+        let file: &dyn File = &other;
+        file.write_path(&config_file).unwrap();
+        assert_eq!(TestConfig::load(&config_file).unwrap(), other);
+    }
+
+    #[test]
+    fn test_load_dir() {
+        let dir = TempDir::new("test_config").unwrap();
+        let entities = dir.path().join("entities");
+        fs::create_dir(&entities).unwrap();
+
+        for (name, baz) in [("alice", 1), ("bob", 2), ("carol", 3)] {
+            let config = TestConfig {
+                foo: name.to_string(),
+                baz,
+                ..TestConfig::default()
+            };
+            config.write(entities.join(format!("{name}.json"))).unwrap();
+        }
+        fs::write(entities.join("README.md"), "ignored").unwrap();
+
+        let configs = load_dir::<TestConfig>(&entities).unwrap();
+
+        assert_eq!(configs.len(), 3);
+        assert_eq!(configs["alice"].baz, 1);
+        assert_eq!(configs["bob"].foo, "bob");
+        assert_eq!(configs["carol"].baz, 3);
+
+        fs::write(entities.join("broken.json"), "{").unwrap();
+        let err = load_dir::<TestConfig>(&entities).unwrap_err();
+        assert!(matches!(&err, Error::InFile { path, .. } if path.ends_with("broken.json")));
+
+        assert!(load_dir::<TestConfig>(dir.path().join("missing"))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[cfg(feature = "path-to-error")]
+    #[test]
+    fn test_field_path_in_error() {
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        struct Server {
+            port: u16,
+        }
+
+        #[derive(Debug, serde::Serialize, serde::Deserialize)]
+        struct Servers {
+            servers: Vec<Server>,
+        }
+
+        impl super::JSONFile for Servers {}
+
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+        fs::write(&config_file, r#"{ "servers": [{ "port": "80" }] }"#).unwrap();
+
+        let err = <Servers as super::JSONFile>::load(&config_file).unwrap_err();
+        assert!(matches!(&err, Error::AtPath { path, .. } if path == "servers[0].port"));
+        assert!(
+            err.to_string().starts_with(
+                "servers[0].port: invalid json: invalid type: string \"80\", expected u16"
+            ),
+            "{err}"
+        );
+        assert_eq!(err.kind(), "json");
+
+        // also when the format is detected
+        let err = crate::format::load_auto::<Servers>(&config_file).unwrap_err();
+        assert!(err.to_string().starts_with("servers[0].port: "), "{err}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_reporting() {
+        let dir = TempDir::new("test_config").unwrap();
+        let target = dir.path().join("dotfiles/config.json");
+        let link = dir.path().join("config.json");
+        fs::create_dir(dir.path().join("dotfiles")).unwrap();
+        fs::write(&target, "{}").unwrap();
+        std::os::unix::fs::symlink(&target, &link).unwrap();
+
+        let written = TestConfig::default().write_reporting(&link).unwrap();
+
+        assert_eq!(written, target.canonicalize().unwrap());
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(TestConfig::load(&target).unwrap(), TestConfig::default());
+    }
+
+    #[test]
+    fn test_write_with_line_ending() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+        let config = TestConfig::default();
+
+        config
+            .write_with_line_ending(&config_file, super::LineEnding::CrLf)
+            .unwrap();
+
+        let content = fs::read_to_string(&config_file).unwrap();
+        assert!(content.contains("{\r\n  \"foo\""), "{content:?}");
+        assert_eq!(
+            content.matches('\n').count(),
+            content.matches("\r\n").count()
+        );
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config);
+        // the temporary file is gone
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&config_file, fs::Permissions::from_mode(0o600)).unwrap();
+            config
+                .write_with_line_ending(&config_file, super::LineEnding::CrLf)
+                .unwrap();
+            let mode = fs::metadata(&config_file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        config
+            .write_with_line_ending(&config_file, super::LineEnding::Lf)
+            .unwrap();
+        assert!(!fs::read_to_string(&config_file).unwrap().contains('\r'));
+
+        assert_eq!(
+            super::LineEnding::Native.resolve(),
+            if cfg!(windows) {
+                super::LineEnding::CrLf
+            } else {
+                super::LineEnding::Lf
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_redacted_string() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Auth {
+            user: String,
+            token: String,
+        }
+
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Secrets {
+            token: String,
+            auth: Auth,
+            port: u16,
+        }
+
+        impl super::JSONFile for Secrets {}
+
+        impl File for Secrets {
+            fn load(path: impl AsRef<std::path::Path>) -> crate::Result<Self> {
+                <Self as super::JSONFile>::load(path)
+            }
+
+            fn write_path(&self, path: &std::path::Path) -> crate::Result<()> {
+                super::JSONFile::write(self, path)
+            }
+        }
+
+        let secrets = Secrets {
+            token: "s3cr3t".to_string(),
+            auth: Auth {
+                user: "alice".to_string(),
+                token: "n3st3d".to_string(),
+            },
+            port: 80,
+        };
+
+        let rendered = secrets
+            .to_redacted_string(
+                crate::format::Format::Json,
+                &["token", "auth.token", "missing.key"],
+            )
+            .unwrap();
+        let rendered: serde_json::Value = serde_json::from_str(&rendered).unwrap();
+
+        assert_eq!(
+            rendered,
+            serde_json::json!({
+                "token": "***",
+                "auth": { "user": "alice", "token": "***" },
+                "port": 80,
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_pretty_string() {
+        let rendered = TestConfig::default()
+            .to_pretty_string(crate::format::Format::Json)
+            .unwrap();
+
+        assert_eq!(
+            rendered,
+            "{\n  \"foo\": \"foo\",\n  \"bar\": true,\n  \"baz\": 42\n}"
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_to_pretty_string_toml() {
+        let rendered = TestConfig::default()
+            .to_pretty_string(crate::format::Format::Toml)
+            .unwrap();
+
+        assert_eq!(rendered, "foo = \"foo\"\nbar = true\nbaz = 42\n");
+    }
+
+    #[test]
+    fn test_load_with_guard() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+        TestConfig::default().write(&config_file).unwrap();
+
+        let (config, guard) = TestConfig::load_with_guard(&config_file).unwrap();
+        assert_eq!(config, TestConfig::default());
+
+        let writer = fs::File::open(&config_file).unwrap();
+        assert!(matches!(
+            writer.try_lock(),
+            Err(fs::TryLockError::WouldBlock)
+        ));
+        // other readers are not blocked
+        let (_, other) = TestConfig::load_with_guard(&config_file).unwrap();
+
+        drop(guard);
+        drop(other);
+        writer.try_lock().unwrap();
+    }
+
+    #[test]
+    fn test_write_journaled() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let journal = dir.path().join("test-config.journal");
+
+        let config = |baz| TestConfig {
+            baz,
+            ..TestConfig::default()
+        };
+
+        config(1).write_journaled(&config_file, &journal).unwrap();
+        config(2).write_journaled(&config_file, &journal).unwrap();
+        config(3).write_journaled(&config_file, &journal).unwrap();
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config(3));
+
+        assert!(undo_last(&config_file, &journal).unwrap());
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config(2));
+
+        assert!(undo_last(&config_file, &journal).unwrap());
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config(1));
+
+        // the first write had nothing to record
+        assert!(!undo_last(&config_file, &journal).unwrap());
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config(1));
+    }
+
+    #[test]
+    fn test_write_guarded() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+
+        // nothing to compare with yet
+        TestConfig::default()
+            .write_guarded(&path, &["foo"])
+            .unwrap();
+
+        let updated = TestConfig {
+            baz: 7,
+            ..TestConfig::default()
+        };
+        updated.write_guarded(&path, &["foo"]).unwrap();
+
+        let changed = TestConfig {
+            foo: "changed".to_string(),
+            ..updated.clone()
+        };
+        let err = changed.write_guarded(&path, &["baz", "foo"]).unwrap_err();
+        assert!(matches!(&err, Error::ImmutableField(field) if field == "foo"));
+        assert_eq!(err.to_string(), "immutable field `foo` cannot be changed");
+        assert_eq!(TestConfig::load(&path).unwrap(), updated);
+
+        // a field missing on disk can be set
+        changed.write_guarded(&path, &["machine_id"]).unwrap();
+    }
+
+    #[test]
+    fn test_write_guarded_set_once() {
+        #[derive(Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Identity {
+            name: String,
+            #[serde(default)]
+            machine_id: Option<String>,
+        }
+
+        impl super::JSONFile for Identity {}
+
+        impl File for Identity {
+            fn load(path: impl AsRef<std::path::Path>) -> crate::Result<Self> {
+                <Self as super::JSONFile>::load(path)
+            }
+
+            fn write_path(&self, path: &std::path::Path) -> crate::Result<()> {
+                super::JSONFile::write(self, path)
+            }
+        }
+
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        fs::write(&path, r#"{ "name": "a" }"#).unwrap();
+
+        let mut identity = Identity {
+            name: "a".to_string(),
+            machine_id: Some("m1".to_string()),
+        };
+        identity.write_guarded(&path, &["machine_id"]).unwrap();
+        assert_eq!(Identity::load(&path).unwrap(), identity);
+
+        identity.machine_id = Some("m2".to_string());
+        assert!(matches!(
+            identity.write_guarded(&path, &["machine_id"]),
+            Err(Error::ImmutableField(field)) if field == "machine_id"
+        ));
+
+        // a `null` on disk isn't set either
+        fs::write(&path, r#"{ "name": "a", "machine_id": null }"#).unwrap();
+        identity.write_guarded(&path, &["machine_id"]).unwrap();
+    }
+
+    #[test]
+    fn test_stage_write() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        TestConfig::default().write(&path).unwrap();
+
+        let updated = TestConfig {
+            foo: "staged".to_string(),
+            ..TestConfig::default()
+        };
+
+        // concurrent writers get their own temporary file
+        let other = TestConfig::default().stage_write(&path).unwrap();
+        let staged = updated.stage_write(&path).unwrap();
+        assert_ne!(staged.temp_path(), other.temp_path());
+        assert_eq!(
+            TestConfig::load(other.temp_path()).unwrap(),
+            TestConfig::default()
+        );
+        other.discard().unwrap();
+
+        assert_eq!(staged.target(), path);
+        assert_eq!(TestConfig::load(staged.temp_path()).unwrap(), updated);
+        assert_eq!(TestConfig::load(&path).unwrap(), TestConfig::default());
+
+        let temp = staged.temp_path().to_path_buf();
+        staged.commit().unwrap();
+        assert!(!temp.exists());
+        assert_eq!(TestConfig::load(&path).unwrap(), updated);
+
+        // only the target is left
+        let files = || fs::read_dir(dir.path()).unwrap().count();
+
+        TestConfig::default()
+            .stage_write(&path)
+            .unwrap()
+            .discard()
+            .unwrap();
+        assert_eq!(files(), 1);
+        assert_eq!(TestConfig::load(&path).unwrap(), updated);
+
+        // dropping the handle discards it as well
+        drop(TestConfig::default().stage_write(&path).unwrap());
+        assert_eq!(files(), 1);
+    }
+
+    #[test]
+    fn test_write_durable() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = TestConfig::default();
+
+        config.write_durable(&config_file).unwrap();
+        assert_eq!(TestConfig::load(&config_file).unwrap(), config);
+    }
+
+    #[test]
+    fn test_load_with_recovery() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = TestConfig::default();
+
+        // valid main file, no recovery needed
+        config.write(&config_file).unwrap();
+        let (loaded_config, recovered) = TestConfig::load_with_recovery(&config_file).unwrap();
+        assert_eq!(config, loaded_config);
+        assert!(!recovered);
+
+        // corrupt main file with a valid backup
+        fs::copy(&config_file, backup_path(&config_file)).unwrap();
+        fs::write(&config_file, "{\"foo\": \"fo").unwrap();
+
+        let (loaded_config, recovered) = TestConfig::load_with_recovery(&config_file).unwrap();
+        assert_eq!(config, loaded_config);
+        assert!(recovered);
+    }
+
+    #[test]
+    fn test_load_with_recovery_not_found() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+
+        // a backup alone must not be used when the main file is missing
+        TestConfig::default()
+            .write(backup_path(&config_file))
+            .unwrap();
+
+        let result = TestConfig::load_with_recovery(&config_file);
+        assert!(matches!(result, Err(crate::error::Error::FileSystem(_))));
+    }
+
+    #[test]
+    fn test_from_deserializer() {
+        let mut deserializer =
+            serde_json::Deserializer::from_str(r#"{ "foo": "in memory", "bar": false, "baz": 1 }"#);
+
+        let config: TestConfig = super::from_deserializer(&mut deserializer).unwrap();
+        deserializer.end().unwrap();
+        assert_eq!(config.foo, "in memory");
+        assert_eq!(config.baz, 1);
+
+        let mut deserializer = serde_json::Deserializer::from_str(r#"{ "foo": 1 }"#);
+        let err = super::from_deserializer::<TestConfig, _>(&mut deserializer).unwrap_err();
+        assert_eq!(err.kind(), "json");
+    }
+
+    #[test]
+    fn test_load_with_bom() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+        fs::write(
+            &config_file,
+            b"\xEF\xBB\xBF{ \"foo\": \"foo\", \"bar\": true, \"baz\": 42 }",
+        )
+        .unwrap();
+
+        assert_eq!(
+            TestConfig::load(&config_file).unwrap(),
+            TestConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_empty_as_default() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+
+        fs::write(&config_file, "").unwrap();
+        let config: TestConfig = empty_as_default(&config_file).unwrap();
+        assert_eq!(config, TestConfig::default());
+
+        fs::write(&config_file, r#"{ "foo": "bar", "bar": false, "baz": 1 }"#).unwrap();
+        let config: TestConfig = empty_as_default(&config_file).unwrap();
+        assert_eq!(config.foo, "bar");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_preserving_metadata() {
+        use std::os::unix::fs::PermissionsExt;
+        use std::path::Path;
+
+        /// Replaces the file instead of truncating it, like an atomic write would
+        struct Replacing(TestConfig);
+
+        impl File for Replacing {
+            fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+                TestConfig::load(path).map(Replacing)
+            }
+
+            fn write_path(&self, path: &Path) -> crate::Result<()> {
+                let tmp = path.with_extension("tmp");
+                self.0.write(&tmp)?;
+                fs::rename(&tmp, path)?;
+
+                Ok(())
+            }
+        }
+
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        let config = Replacing(TestConfig::default());
+
+        config.write(&config_file).unwrap();
+        fs::set_permissions(&config_file, fs::Permissions::from_mode(0o640)).unwrap();
+
+        config.write_preserving_metadata(&config_file).unwrap();
+
+        let mode = fs::metadata(&config_file).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o640);
+        assert_eq!(
+            Replacing::load(&config_file).unwrap().0,
+            TestConfig::default()
+        );
+    }
+
+    #[test]
+    fn test_load_many() {
+        let dir = TempDir::new("test_config").unwrap();
+        let paths: Vec<_> = (0..12)
+            .map(|idx| dir.path().join(format!("config-{idx}.json")))
+            .collect();
+
+        for (idx, path) in paths.iter().enumerate() {
+            let config = TestConfig {
+                baz: idx as u32,
+                ..TestConfig::default()
+            };
+            config.write(path).unwrap();
+        }
+
+        fs::write(&paths[5], "{ corrupt").unwrap();
+
+        let results = load_many::<TestConfig>(paths);
+        assert_eq!(results.len(), 12);
+
+        for (idx, result) in results.into_iter().enumerate() {
+            match idx {
+                5 => assert!(result.is_err()),
+                _ => assert_eq!(result.unwrap().baz, idx as u32),
+            }
+        }
+    }
+
+    impl PostLoad for TestConfig {
+        fn post_load(&mut self) -> crate::Result<()> {
+            self.foo = self.foo.to_uppercase();
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_load_transformed() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        TestConfig::default().write(&config_file).unwrap();
+
+        let config = TestConfig::load_transformed(&config_file).unwrap();
+        assert_eq!(config.foo, "FOO");
+
+        // plain `load` is untouched
+        assert_eq!(TestConfig::load(&config_file).unwrap().foo, "foo");
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod borrowed_tests {
+    use super::{load_borrowed, read_bytes};
+    use std::borrow::Cow;
+    use tempdir::TempDir;
+
+    #[derive(serde::Deserialize)]
+    struct BorrowedConfig<'a> {
+        #[serde(borrow)]
+        name: Cow<'a, str>,
+        path: &'a str,
+    }
+
+    #[test]
+    fn test_load_borrowed() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+        std::fs::write(&config_file, r#"{ "name": "borrowed", "path": "/tmp" }"#).unwrap();
+
+        let bytes = read_bytes(&config_file).unwrap();
+        let config: BorrowedConfig = load_borrowed(&bytes).unwrap();
+
+        assert!(matches!(config.name, Cow::Borrowed("borrowed")));
+        assert_eq!(config.path, "/tmp");
+
+        // both fields point into the buffer
+        let buffer = bytes.as_ptr_range();
+        assert!(buffer.contains(&config.name.as_ptr()));
+        assert!(buffer.contains(&config.path.as_ptr()));
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod stream_tests {
+    use super::write_map_stream;
+    use std::collections::HashMap;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_write_map_stream() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("test-config.json");
+
+        let file = std::fs::File::create(&config_file).unwrap();
+        let entries = (0..10_000).map(|idx| (format!("key-{idx}"), serde_json::json!(idx)));
+        write_map_stream(std::io::BufWriter::new(file), entries).unwrap();
+
+        let content = std::fs::read(&config_file).unwrap();
+        let map: HashMap<String, u32> = serde_json::from_slice(&content).unwrap();
+
+        assert_eq!(map.len(), 10_000);
+        assert_eq!(map["key-0"], 0);
+        assert_eq!(map["key-9999"], 9999);
+    }
+
+    #[test]
+    fn test_write_empty_map_stream() {
+        let mut buffer = Vec::new();
+        write_map_stream(&mut buffer, std::iter::empty()).unwrap();
+
+        assert_eq!(buffer, b"{}");
+    }
+}
+
+#[cfg(test)]
+mod sanitize_tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_input_bom() {
+        assert_eq!(sanitize_input(b"\xEF\xBB\xBF{}"), b"{}");
+        assert_eq!(sanitize_input(b"\xEF\xBB\xBF\n\n  a: 1\n"), b"  a: 1\n");
+    }
+
+    #[test]
+    fn test_sanitize_input_no_bom() {
+        assert_eq!(sanitize_input(b"{}"), b"{}");
+        assert_eq!(sanitize_input(b" \r\n\t\n  a: 1\n"), b"  a: 1\n");
+        // a BOM in the middle is content
+        assert_eq!(sanitize_input(b"a\xEF\xBB\xBF"), b"a\xEF\xBB\xBF");
+    }
+
+    #[test]
+    fn test_sanitize_input_empty() {
+        assert_eq!(sanitize_input(b""), b"");
+        assert_eq!(sanitize_input(b"\xEF\xBB\xBF"), b"");
     }
 }
@@ -1,5 +1,4 @@
 #[deny(missing_docs)]
-
 /// Core Module
 mod core;
 pub use crate::core::*;
@@ -9,5 +8,65 @@ pub mod error;
 
 pub mod fs;
 
+/// Supported formats
+pub mod format;
+
+/// Layered configs
+pub mod merge;
+
+/// Chained config sources
+#[cfg(feature = "sources")]
+pub mod sources;
+
+/// Enum variants handling
+pub mod enums;
+
+/// Generic config values
+pub mod value;
+
+/// Environment variables
+pub mod env;
+
+/// Load options
+pub mod options;
+
+/// Named profiles
+pub mod profile;
+
+/// Config versioning
+pub mod version;
+
+/// Shell export
+pub mod shell;
+
+/// Config comparison
+pub mod diff;
+
+/// `config` subcommand
+#[cfg(feature = "clap")]
+pub mod command;
+
+/// Storage abstraction
+pub mod store;
+
+/// Zip archives
+#[cfg(feature = "zip")]
+pub mod archive;
+
+/// Remote configs
+#[cfg(feature = "http")]
+pub mod remote;
+
+/// Figment interop
+#[cfg(feature = "figment")]
+pub mod provider;
+
+/// JSON schema
+#[cfg(feature = "schema")]
+pub mod schema;
+
 // wrap default result type and inject local crate error
 pub type Result<T> = std::result::Result<T, error::Error>;
+
+#[cfg(test)]
+mod test_utils;
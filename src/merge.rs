@@ -0,0 +1,615 @@
+/*!
+  This module contains the utilities to layer
+  multiple config sources on top of each other.
+*/
+
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+use std::{collections::HashMap, fs, io, path::Path};
+
+use crate::{error::Error, format::Format, value};
+
+/// Types that can have a (partial) config merged on top of them.
+///
+/// The default implementation deep-merges objects key by key,
+/// any other value (arrays included) is replaced.
+///
+/// ```
+/// use cli_config::merge::Merge;
+///
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// struct MyConfig {
+///     pub name: String,
+///     pub verbose: bool,
+/// }
+///
+/// impl Merge for MyConfig {}
+/// ```
+pub trait Merge
+where
+    Self: DeserializeOwned + Serialize,
+{
+    /// Merge `other` on top of `self`, values from `other` take precedence
+    fn merge(self, other: Value) -> crate::Result<Self> {
+        let mut base = serde_json::to_value(self)?;
+        Self::merge_value(&mut base, other);
+
+        serde_json::from_value(base).map_err(Error::JSON)
+    }
+
+    /// Merge the (possibly partial) `other` on top of `base`
+    ///
+    /// Used when layering raw values before deserializing `Self`,
+    /// it defaults to [`merge_values`].
+    fn merge_value(base: &mut Value, other: Value) {
+        merge_values(base, other)
+    }
+}
+
+/// Deep-merge `other` into `base`
+pub fn merge_values(base: &mut Value, other: Value) {
+    match (base, other) {
+        (Value::Object(base), Value::Object(other)) => {
+            for (key, value) in other {
+                match base.get_mut(&key) {
+                    Some(current) => merge_values(current, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, other) => *base = other,
+    }
+}
+
+/// Load the config at `path` on top of a default config embedded in the binary
+/// (e.g. via `include_str!`).
+///
+/// Both `embedded` and the file at `path` are parsed using `format`, the file
+/// being read with [`crate::fs::read_config`] (an empty one is an error).
+/// If `path` does not exist the embedded config is returned as-is.
+pub fn load_over_embedded<T>(
+    path: impl AsRef<Path>,
+    embedded: &str,
+    format: Format,
+) -> crate::Result<T>
+where
+    T: Merge,
+{
+    let path = path.as_ref();
+    let base: T = format.parse(embedded)?;
+
+    match crate::fs::read_config(path) {
+        Ok(content) => format
+            .parse(&content)
+            .and_then(|overrides| base.merge(overrides))
+            .map_err(|err| err.in_file(path)),
+        Err(Error::FileSystem(err)) if err.kind() == io::ErrorKind::NotFound => Ok(base),
+        Err(err) => Err(err),
+    }
+}
+
+/// Load the config located by [`crate::locate_config`] and merge the drop-in
+/// fragments found in the sibling `{base}.d/` directory on top of it.
+///
+/// Given `config.json`, every `config.d/*.json` file is merged in lexical order.
+/// Only fragments sharing the extension of the base file are considered and
+/// a missing (or empty) drop-in directory is not an error.
+pub fn load_with_dropins<T>(prefix: &str, base_filename: &str) -> crate::Result<T>
+where
+    T: Merge,
+{
+    let base_path = crate::locate_config(prefix, base_filename).ok_or(Error::FileNotFound)?;
+    let mut config: T = value::from_value(value::load_value(&base_path)?)?;
+
+    for fragment in dropins(&base_path)? {
+        let fragment_value = value::load_value(&fragment).map_err(|err| err.in_file(&fragment))?;
+
+        config = config
+            .merge(fragment_value)
+            .map_err(|err| err.in_file(&fragment))?;
+    }
+
+    Ok(config)
+}
+
+/// List the drop-in fragments of `base_path` sorted lexically
+fn dropins(base_path: &Path) -> crate::Result<Vec<std::path::PathBuf>> {
+    let (Some(stem), Some(parent)) = (base_path.file_stem(), base_path.parent()) else {
+        return Ok(Vec::new());
+    };
+
+    let mut dir_name = stem.to_os_string();
+    dir_name.push(".d");
+
+    let entries = match fs::read_dir(parent.join(dir_name)) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::FileSystem(err)),
+    };
+
+    let mut fragments = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+
+        // compared by format so that `.yml` and `.yaml` are equivalent
+        let same_format = match Format::from_path(base_path) {
+            Some(format) => Format::from_path(&path) == Some(format),
+            None => path.extension() == base_path.extension(),
+        };
+
+        if path.is_file() && same_format {
+            fragments.push(path);
+        }
+    }
+
+    fragments.sort();
+
+    Ok(fragments)
+}
+
+/// The layer which supplied a value, see [`load_with_provenance`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// `T::default()`
+    Default,
+    /// The config file located by [`crate::locate_config`]
+    File,
+    /// The profile given to [`load_with_provenance`]
+    Profile,
+    /// An environment variable
+    Env,
+}
+
+/// Load `T` from the following layers, each one merged on top of the previous:
+///
+/// 1. `T::default()`
+/// 2. the config located by [`crate::locate_config`], if any
+/// 3. the `profile`, if given (see [`crate::profile::load_with_profile`])
+/// 4. the environment variables `{env_prefix}_{PATH}`, e.g. `MYAPP_SERVER_PORT`
+///    for `server.port`
+///
+/// Alongside the config, the layer which supplied the final value of every
+/// leaf field is returned, keyed by its dotted path (e.g. `server.port`).
+/// Arrays are leaves. Only the fields known after the profile layer
+/// can be set from the environment, their variable is read as a string
+/// falling back to its JSON value for non-string fields.
+pub fn load_with_provenance<T>(
+    prefix: &str,
+    filename: &str,
+    profile: Option<&str>,
+    env_prefix: &str,
+) -> crate::Result<(T, HashMap<String, Source>)>
+where
+    T: Merge + Default,
+{
+    let mut config = serde_json::to_value(T::default())?;
+    let mut provenance = HashMap::new();
+    record_provenance(&mut provenance, "", &config, Source::Default);
+
+    if let Some(path) = crate::locate_config(prefix, filename) {
+        let layer = value::load_value(&path).map_err(|err| err.in_file(&path))?;
+        apply_layer::<T>(&mut config, &mut provenance, layer, Source::File);
+    }
+
+    if let Some(name) = profile {
+        let path = crate::profile::profile_path(prefix, name)
+            .filter(|path| path.exists())
+            .ok_or_else(|| Error::ProfileNotFound(name.to_string()))?;
+        let layer = value::load_value(&path).map_err(|err| err.in_file(&path))?;
+        apply_layer::<T>(&mut config, &mut provenance, layer, Source::Profile);
+    }
+
+    let layer = env_layer(&config, env_prefix);
+    apply_layer::<T>(&mut config, &mut provenance, layer, Source::Env);
+
+    Ok((value::from_value(config)?, provenance))
+}
+
+/// Merge `layer` into `config` recording `source` for each of its leaves
+fn apply_layer<T>(
+    config: &mut Value,
+    provenance: &mut HashMap<String, Source>,
+    layer: Value,
+    source: Source,
+) where
+    T: Merge,
+{
+    record_provenance(provenance, "", &layer, source);
+    T::merge_value(config, layer);
+}
+
+/// Record `source` for every leaf of `value`, located at `path`
+fn record_provenance(
+    provenance: &mut HashMap<String, Source>,
+    path: &str,
+    value: &Value,
+    source: Source,
+) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                let path = match path {
+                    "" => key.clone(),
+                    _ => format!("{path}.{key}"),
+                };
+
+                record_provenance(provenance, &path, value, source);
+            }
+        }
+        _ if path.is_empty() => {}
+        _ => {
+            // a leaf replacing an object replaces its fields as well
+            let nested = format!("{path}.");
+            provenance.retain(|key, _| !key.starts_with(&nested));
+            provenance.insert(path.to_string(), source);
+        }
+    }
+}
+
+/// Build the layer of the leaves of `config` set by an environment variable
+pub(crate) fn env_layer(config: &Value, env_prefix: &str) -> Value {
+    let mut leaves = Vec::new();
+    collect_leaves(config, &mut Vec::new(), &mut leaves);
+
+    let mut layer = Value::Object(Default::default());
+
+    for (path, current) in leaves {
+        let var = format!("{env_prefix}_{}", path.join("_"))
+            .to_uppercase()
+            .replace('-', "_");
+
+        let Ok(raw) = std::env::var(var) else {
+            continue;
+        };
+
+        let value = match current {
+            Value::String(_) => Value::String(raw),
+            _ => serde_json::from_str(&raw).unwrap_or(Value::String(raw)),
+        };
+
+        let mut target = &mut layer;
+        for key in &path {
+            target = &mut target[key.as_str()];
+        }
+
+        *target = value;
+    }
+
+    layer
+}
+
+/// Collect the leaves of `value` along with their path
+fn collect_leaves<'a>(
+    value: &'a Value,
+    path: &mut Vec<String>,
+    leaves: &mut Vec<(Vec<String>, &'a Value)>,
+) {
+    match value {
+        Value::Object(map) if !map.is_empty() => {
+            for (key, value) in map {
+                path.push(key.clone());
+                collect_leaves(value, path, leaves);
+                path.pop();
+            }
+        }
+        _ if path.is_empty() => {}
+        _ => leaves.push((path.clone(), value)),
+    }
+}
+
+/// Key listing the files to include
+const INCLUDE_KEY: &str = "include";
+
+/// Load the config at `path` resolving its `include` directive.
+///
+/// `include` is an array of paths, relative to the directory of the config
+/// listing them, which are merged in order *under* the current file (i.e. the
+/// including file wins). Included files can include other files, a cycle
+/// results in `Error::IncludeCycle`.
+pub fn load_with_includes<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: Merge,
+{
+    let config = resolve_includes::<T>(path.as_ref(), &mut Vec::new())?;
+
+    value::from_value(config)
+}
+
+/// Resolve the includes of `path`, `stack` holds the files being resolved
+fn resolve_includes<T>(path: &Path, stack: &mut Vec<std::path::PathBuf>) -> crate::Result<Value>
+where
+    T: Merge,
+{
+    let canonical = path
+        .canonicalize()
+        .map_err(|err| Error::from(err).in_file(path))?;
+
+    if stack.contains(&canonical) {
+        return Err(Error::IncludeCycle(path.to_path_buf()));
+    }
+
+    let mut current = value::load_value(path).map_err(|err| err.in_file(path))?;
+    let includes = match current.as_object_mut() {
+        Some(map) => map.remove(INCLUDE_KEY),
+        None => None,
+    };
+
+    let includes = match includes {
+        None => return Ok(current),
+        Some(Value::Array(includes)) => includes,
+        Some(_) => {
+            return Err(Error::InvalidConfig("`include` must be an array of paths").in_file(path))
+        }
+    };
+
+    stack.push(canonical);
+
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut config = Value::Object(Default::default());
+
+    for include in includes {
+        let Value::String(include) = include else {
+            return Err(Error::InvalidConfig("`include` must be an array of paths").in_file(path));
+        };
+
+        let included = resolve_includes::<T>(&dir.join(include), stack)?;
+        T::merge_value(&mut config, included);
+    }
+
+    T::merge_value(&mut config, current);
+    stack.pop();
+
+    Ok(config)
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TempHome, TestConfig};
+    use tempdir::TempDir;
+
+    impl Merge for TestConfig {}
+
+    const EMBEDDED: &str = r#"{ "foo": "embedded", "bar": false, "baz": 1 }"#;
+
+    #[test]
+    fn test_load_over_embedded() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+        fs::write(&config_file, r#"{ "baz": 42 }"#).unwrap();
+
+        let config: TestConfig = load_over_embedded(&config_file, EMBEDDED, Format::Json).unwrap();
+
+        assert_eq!(config.foo, "embedded");
+        assert!(!config.bar);
+        assert_eq!(config.baz, 42);
+    }
+
+    #[test]
+    fn test_load_over_embedded_missing_file() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+
+        let config: TestConfig = load_over_embedded(&config_file, EMBEDDED, Format::Json).unwrap();
+
+        assert_eq!(config.foo, "embedded");
+        assert_eq!(config.baz, 1);
+    }
+
+    #[test]
+    fn test_load_over_embedded_read_config() {
+        let dir = TempDir::new("test_config").unwrap();
+        let config_file = dir.path().join("config.json");
+
+        fs::write(&config_file, "\u{feff}{ \"baz\": 42 }").unwrap();
+        let config: TestConfig = load_over_embedded(&config_file, EMBEDDED, Format::Json).unwrap();
+        assert_eq!(config.baz, 42);
+
+        fs::write(&config_file, "  \n").unwrap();
+        assert!(matches!(
+            load_over_embedded::<TestConfig>(&config_file, EMBEDDED, Format::Json),
+            Err(Error::EmptyConfig(path)) if path == config_file
+        ));
+
+        fs::write(&config_file, r#"{ "baz": "#).unwrap();
+        let err =
+            load_over_embedded::<TestConfig>(&config_file, EMBEDDED, Format::Json).unwrap_err();
+        assert!(matches!(&err, Error::InFile { path, .. } if path == &config_file));
+    }
+
+    #[test]
+    fn test_merge_values() {
+        let mut base = serde_json::json!({ "a": { "b": 1, "c": 2 }, "d": [1, 2] });
+        merge_values(&mut base, serde_json::json!({ "a": { "c": 3 }, "d": [3] }));
+
+        assert_eq!(
+            base,
+            serde_json::json!({ "a": { "b": 1, "c": 3 }, "d": [3] })
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_load_with_dropins() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        fs::create_dir_all(dir.join("config.d")).unwrap();
+
+        fs::write(
+            dir.join("config.json"),
+            r#"{ "foo": "base", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+        fs::write(dir.join("config.d/10-foo.json"), r#"{ "foo": "first" }"#).unwrap();
+        fs::write(dir.join("config.d/20-baz.json"), r#"{ "baz": 2 }"#).unwrap();
+        fs::write(dir.join("config.d/30-foo.json"), r#"{ "foo": "last" }"#).unwrap();
+        // other extensions are ignored
+        fs::write(dir.join("config.d/40-baz.toml"), "baz = 3").unwrap();
+
+        let config: TestConfig = load_with_dropins("my-app", "config.json").unwrap();
+
+        assert_eq!(config.foo, "last");
+        assert!(config.bar);
+        assert_eq!(config.baz, 2);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_load_with_dropins_without_dir() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("config.json"),
+            r#"{ "foo": "base", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+
+        let config: TestConfig = load_with_dropins("my-app", "config.json").unwrap();
+        assert_eq!(config.foo, "base");
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_load_with_dropins_malformed_fragment() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        fs::create_dir_all(dir.join("config.d")).unwrap();
+        fs::write(
+            dir.join("config.json"),
+            r#"{ "foo": "base", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+        fs::write(dir.join("config.d/10-broken.json"), r#"{ "foo": "#).unwrap();
+
+        let err = load_with_dropins::<TestConfig>("my-app", "config.json").unwrap_err();
+        assert!(matches!(&err, Error::InFile { path, .. } if path.ends_with("10-broken.json")));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_load_with_provenance() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        fs::create_dir_all(dir.join("profiles")).unwrap();
+        fs::write(dir.join("config.json"), r#"{ "foo": "file", "baz": 1 }"#).unwrap();
+        fs::write(dir.join("profiles/work.json"), r#"{ "baz": 2 }"#).unwrap();
+        std::env::set_var("PROVENANCE_TEST_BAZ", "3");
+
+        let result = load_with_provenance::<TestConfig>(
+            "my-app",
+            "config.json",
+            Some("work"),
+            "PROVENANCE_TEST",
+        );
+        std::env::remove_var("PROVENANCE_TEST_BAZ");
+
+        let (config, provenance) = result.unwrap();
+
+        assert_eq!(config.foo, "file");
+        assert!(config.bar);
+        assert_eq!(config.baz, 3);
+        assert_eq!(
+            provenance,
+            HashMap::from([
+                ("foo".to_string(), Source::File),
+                ("bar".to_string(), Source::Default),
+                ("baz".to_string(), Source::Env),
+            ])
+        );
+
+        assert!(matches!(
+            load_with_provenance::<TestConfig>(
+                "my-app",
+                "config.json",
+                Some("home"),
+                "PROVENANCE_TEST"
+            ),
+            Err(Error::ProfileNotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_record_provenance() {
+        let mut provenance = HashMap::new();
+
+        record_provenance(
+            &mut provenance,
+            "",
+            &serde_json::json!({ "server": { "host": "a", "port": 1 }, "tags": [] }),
+            Source::Default,
+        );
+        record_provenance(
+            &mut provenance,
+            "",
+            &serde_json::json!({ "server": { "port": 2 } }),
+            Source::File,
+        );
+
+        assert_eq!(provenance["server.host"], Source::Default);
+        assert_eq!(provenance["server.port"], Source::File);
+        assert_eq!(provenance["tags"], Source::Default);
+
+        record_provenance(
+            &mut provenance,
+            "",
+            &serde_json::json!({ "server": "none" }),
+            Source::Env,
+        );
+
+        assert_eq!(
+            provenance,
+            HashMap::from([
+                ("server".to_string(), Source::Env),
+                ("tags".to_string(), Source::Default),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_load_with_includes() {
+        let dir = TempDir::new("test_config").unwrap();
+        fs::create_dir(dir.path().join("shared")).unwrap();
+
+        fs::write(
+            dir.path().join("shared/base.json"),
+            r#"{ "foo": "base", "bar": false, "baz": 1 }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("shared/overrides.json"),
+            r#"{ "include": ["base.json"], "baz": 2 }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("config.json"),
+            r#"{ "include": ["shared/overrides.json"], "foo": "main" }"#,
+        )
+        .unwrap();
+
+        let config: TestConfig = load_with_includes(dir.path().join("config.json")).unwrap();
+
+        assert_eq!(config.foo, "main");
+        assert!(!config.bar);
+        assert_eq!(config.baz, 2);
+    }
+
+    #[test]
+    fn test_load_with_includes_cycle() {
+        let dir = TempDir::new("test_config").unwrap();
+
+        fs::write(
+            dir.path().join("a.json"),
+            r#"{ "include": ["b.json"], "foo": "a", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("b.json"), r#"{ "include": ["a.json"] }"#).unwrap();
+
+        let err = load_with_includes::<TestConfig>(&dir.path().join("a.json")).unwrap_err();
+        assert!(matches!(err, Error::IncludeCycle(path) if path.ends_with("a.json")));
+    }
+}
@@ -0,0 +1,328 @@
+/*!
+  This module gathers the strictness knobs applied while
+  loading a config behind a single [`LoadOptions`].
+*/
+
+use serde::de::{DeserializeOwned, Error as _, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+use std::{collections::HashSet, fmt, path::Path};
+
+use crate::{error::Error, format::Format, value};
+
+/// What to do with a key appearing twice in the same object
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicateKeys {
+    /// Keep the last value
+    ///
+    /// Formats which reject duplicate keys by themselves still do.
+    #[default]
+    LastWins,
+    /// Fail naming the duplicate key
+    Reject,
+}
+
+/// What to do with a top-level key matching no field of the config
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnknownFields {
+    /// Silently ignore it
+    #[default]
+    Ignore,
+    /// Fail naming the unknown field
+    Reject,
+}
+
+/// Options of [`load_with_options`], the default being as lenient as [`crate::fs::File::load`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LoadOptions {
+    /// Handling of duplicate keys
+    pub duplicate_keys: DuplicateKeys,
+    /// Handling of unknown top-level fields
+    pub unknown_fields: UnknownFields,
+    /// Deepest nesting of objects/arrays accepted, checked before parsing
+    /// as [`value::load_limited_depth`] does
+    pub max_depth: Option<usize>,
+    /// Largest file accepted, in bytes
+    pub max_size: Option<u64>,
+}
+
+/// Load the config at `path` into `T` using `format` and applying `options`
+///
+/// ```
+/// use cli_config::options::{DuplicateKeys, LoadOptions};
+///
+/// let options = LoadOptions {
+///     duplicate_keys: DuplicateKeys::Reject,
+///     max_size: Some(64 * 1024),
+///     ..LoadOptions::default()
+/// };
+/// ```
+pub fn load_with_options<T>(
+    path: impl AsRef<Path>,
+    format: Format,
+    options: &LoadOptions,
+) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+
+    if let Some(max_size) = options.max_size {
+        if std::fs::metadata(path)?.len() > max_size {
+            return Err(Error::InvalidConfig("config file too large"));
+        }
+    }
+
+    let content = crate::fs::read_config(path)?;
+
+    if let Some(max_depth) = options.max_depth {
+        value::check_raw_depth(&content, format, max_depth)?;
+    }
+
+    if options.duplicate_keys == DuplicateKeys::Reject {
+        format.parse::<UniqueKeys>(&content)?;
+    }
+
+    let config: Value = format.parse(&content)?;
+
+    if let Some(max_depth) = options.max_depth {
+        if value::depth(&config) > max_depth {
+            return Err(Error::InvalidConfig("config nesting too deep"));
+        }
+    }
+
+    if options.unknown_fields == UnknownFields::Reject {
+        reject_unknown_fields::<T>(&config)?;
+    }
+
+    value::from_value(config)
+}
+
+/// Fail on the first top-level key of `config` which isn't a field of `T`
+///
+/// Nothing is checked when `T` isn't a struct.
+fn reject_unknown_fields<T>(config: &Value) -> crate::Result<()>
+where
+    T: DeserializeOwned,
+{
+    let fields = value::struct_fields::<T>();
+
+    let Value::Object(map) = config else {
+        return Ok(());
+    };
+
+    if fields.is_empty() {
+        return Ok(());
+    }
+
+    match map.keys().find(|key| !fields.contains(&key.as_str())) {
+        Some(key) => Err(Error::validation(format!("unknown field `{key}`"))),
+        None => Ok(()),
+    }
+}
+
+/// A document only deserializing when none of its objects has a key twice
+struct UniqueKeys;
+
+impl<'de> Deserialize<'de> for UniqueKeys {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(UniqueKeysVisitor)
+    }
+}
+
+struct UniqueKeysVisitor;
+
+impl<'de> Visitor<'de> for UniqueKeysVisitor {
+    type Value = UniqueKeys;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value")
+    }
+
+    fn visit_bool<E>(self, _: bool) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_i64<E>(self, _: i64) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_u64<E>(self, _: u64) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_f64<E>(self, _: f64) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_str<E>(self, _: &str) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_bytes<E>(self, _: &[u8]) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_none<E>(self) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_unit<E>(self) -> Result<Self::Value, E> {
+        Ok(UniqueKeys)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Self::Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        UniqueKeys::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        while seq.next_element::<UniqueKeys>()?.is_some() {}
+
+        Ok(UniqueKeys)
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut keys = HashSet::new();
+
+        // keys aren't necessarily strings (e.g. in YAML)
+        while let Some(key) = map.next_key::<Value>()? {
+            let key = match key {
+                Value::String(key) => key,
+                key => key.to_string(),
+            };
+
+            if !keys.insert(key.clone()) {
+                return Err(A::Error::custom(format!("duplicate key: `{key}`")));
+            }
+
+            map.next_value::<UniqueKeys>()?;
+        }
+
+        Ok(UniqueKeys)
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestConfig;
+    use tempdir::TempDir;
+
+    const CONFIG: &str = r#"{ "foo": "first", "bar": true, "baz": 1, "foo": "last" }"#;
+
+    fn write(dir: &TempDir, content: &str) -> std::path::PathBuf {
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, content).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_duplicate_keys() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write(&dir, CONFIG);
+
+        let config: TestConfig =
+            load_with_options(&path, Format::Json, &LoadOptions::default()).unwrap();
+        assert_eq!(config.foo, "last");
+
+        let options = LoadOptions {
+            duplicate_keys: DuplicateKeys::Reject,
+            ..LoadOptions::default()
+        };
+        let err = load_with_options::<TestConfig>(&path, Format::Json, &options).unwrap_err();
+        assert!(err.to_string().contains("duplicate key: `foo`"), "{err}");
+
+        // nested objects are checked too
+        let path = write(&dir, r#"{ "nested": [{ "a": 1, "a": 2 }] }"#);
+        assert!(load_with_options::<Value>(&path, Format::Json, &options).is_err());
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write(&dir, r#"{ "foo": "foo", "bar": true, "baz": 1, "qux": 2 }"#);
+
+        assert!(
+            load_with_options::<TestConfig>(&path, Format::Json, &LoadOptions::default()).is_ok()
+        );
+
+        let options = LoadOptions {
+            unknown_fields: UnknownFields::Reject,
+            ..LoadOptions::default()
+        };
+        let err = load_with_options::<TestConfig>(&path, Format::Json, &options).unwrap_err();
+        assert!(matches!(&err, Error::Validation(message) if message == "unknown field `qux`"));
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_toml_options() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.toml");
+
+        std::fs::write(&path, "foo = \"foo\"\nbar = true\nbaz = 1\nqux = 2\n").unwrap();
+        let options = LoadOptions {
+            unknown_fields: UnknownFields::Reject,
+            ..LoadOptions::default()
+        };
+        assert!(matches!(
+            load_with_options::<TestConfig>(&path, Format::Toml, &options),
+            Err(Error::Validation(message)) if message == "unknown field `qux`"
+        ));
+
+        // would overflow the stack of the parser
+        let levels = 200_000;
+        std::fs::write(
+            &path,
+            format!("a = {}{}\n", "[".repeat(levels), "]".repeat(levels)),
+        )
+        .unwrap();
+        let options = LoadOptions {
+            max_depth: Some(16),
+            duplicate_keys: DuplicateKeys::Reject,
+            ..LoadOptions::default()
+        };
+        assert!(matches!(
+            load_with_options::<Value>(&path, Format::Toml, &options),
+            Err(Error::InvalidConfig("config nesting too deep"))
+        ));
+    }
+
+    #[test]
+    fn test_limits() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write(&dir, r#"{ "a": { "b": { "c": 1 } } }"#);
+
+        let options = LoadOptions {
+            max_depth: Some(2),
+            ..LoadOptions::default()
+        };
+        assert!(matches!(
+            load_with_options::<Value>(&path, Format::Json, &options),
+            Err(Error::InvalidConfig("config nesting too deep"))
+        ));
+
+        let options = LoadOptions {
+            max_size: Some(8),
+            ..LoadOptions::default()
+        };
+        assert!(matches!(
+            load_with_options::<Value>(&path, Format::Json, &options),
+            Err(Error::InvalidConfig("config file too large"))
+        ));
+    }
+}
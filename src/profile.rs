@@ -0,0 +1,241 @@
+/*!
+  This module contains the utilities to manage named
+  profiles stored as `{prefix}/profiles/{name}.json`.
+*/
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    core::{get_config_dir, is_plain_name},
+    error::Error,
+    fs::File,
+    merge::Merge,
+    value,
+};
+
+const PROFILES_DIR: &str = "profiles";
+const PROFILE_EXTENSION: &str = "json";
+
+/// Get the directory containing the profiles of `prefix`
+pub fn profiles_dir(prefix: &str) -> Option<PathBuf> {
+    get_config_dir(prefix).map(|dir| dir.join(PROFILES_DIR))
+}
+
+/// Get the path of the profile `name`, whether it exists or not
+///
+/// Returns `None` when `name` (or `prefix`) could escape the profiles directory.
+pub fn profile_path(prefix: &str, name: &str) -> Option<PathBuf> {
+    if !is_plain_name(name) {
+        return None;
+    }
+
+    profiles_dir(prefix).map(|dir| dir.join(format!("{name}.{PROFILE_EXTENSION}")))
+}
+
+/// List the names of the available profiles, sorted alphabetically
+///
+/// A missing profiles directory results in an empty list.
+pub fn list_profiles(prefix: &str) -> crate::Result<Vec<String>> {
+    let Some(dir) = profiles_dir(prefix) else {
+        return Ok(Vec::new());
+    };
+
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(Error::FileSystem(err)),
+    };
+
+    let mut profiles = Vec::new();
+
+    for entry in entries {
+        let path = entry?.path();
+
+        if let Some(name) = profile_name(&path) {
+            profiles.push(name);
+        }
+    }
+
+    profiles.sort();
+
+    Ok(profiles)
+}
+
+/// Get the profile name of `path`, if it's a profile file
+fn profile_name(path: &Path) -> Option<String> {
+    if !path.is_file() || path.extension()? != PROFILE_EXTENSION {
+        return None;
+    }
+
+    path.file_stem()?.to_str().map(String::from)
+}
+
+/// Reject a profile `name` which could escape the profiles directory
+fn validate_name(name: &str) -> crate::Result<()> {
+    if !is_plain_name(name) {
+        return Err(Error::InvalidConfig("invalid profile name"));
+    }
+
+    Ok(())
+}
+
+/// Load the profile `name`
+pub fn load_profile<T>(prefix: &str, name: &str) -> crate::Result<T>
+where
+    T: File,
+{
+    validate_name(name)?;
+
+    let path = profile_path(prefix, name)
+        .filter(|path| path.exists())
+        .ok_or(Error::FileNotFound)?;
+
+    T::load(&path)
+}
+
+/// Save `config` as the profile `name`, creating the profiles directory if needed
+///
+/// A `name` which could escape the profiles directory (e.g. `../config`)
+/// fails with `Error::InvalidConfig`.
+pub fn save_profile<T>(config: &T, prefix: &str, name: &str) -> crate::Result<PathBuf>
+where
+    T: File,
+{
+    validate_name(name)?;
+
+    let path = profile_path(prefix, name).ok_or(Error::Custom("Could not create file"))?;
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    config.write(&path)?;
+
+    Ok(path)
+}
+
+/// Load the config located by [`crate::locate_config`] and, when `profile`
+/// is given, merge the profile of that name on top of it
+/// (e.g. from a `--profile <name>` flag).
+///
+/// Returns `Error::ProfileNotFound` when the requested profile doesn't exist,
+/// and `Error::InvalidConfig` when its name isn't a plain file name.
+pub fn load_with_profile<T>(
+    prefix: &str,
+    base_filename: &str,
+    profile: Option<&str>,
+) -> crate::Result<T>
+where
+    T: Merge,
+{
+    let base_path = crate::locate_config(prefix, base_filename).ok_or(Error::FileNotFound)?;
+    let config: T = value::from_value(value::load_value(&base_path)?)?;
+
+    let Some(name) = profile else {
+        return Ok(config);
+    };
+
+    validate_name(name)?;
+
+    let path = profile_path(prefix, name)
+        .filter(|path| path.exists())
+        .ok_or_else(|| Error::ProfileNotFound(name.to_string()))?;
+    let overrides = value::load_value(&path).map_err(|err| err.in_file(&path))?;
+
+    config.merge(overrides).map_err(|err| err.in_file(&path))
+}
+
+#[cfg(all(feature = "json", not(windows)))]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TempHome, TestConfig};
+
+    #[test]
+    fn test_profiles() {
+        let _home = TempHome::new();
+
+        assert!(list_profiles("my-app").unwrap().is_empty());
+
+        let work = TestConfig {
+            foo: "work".to_string(),
+            ..TestConfig::default()
+        };
+        let personal = TestConfig {
+            foo: "personal".to_string(),
+            ..TestConfig::default()
+        };
+
+        save_profile(&work, "my-app", "work").unwrap();
+        save_profile(&personal, "my-app", "personal").unwrap();
+
+        assert_eq!(list_profiles("my-app").unwrap(), vec!["personal", "work"]);
+        assert_eq!(load_profile::<TestConfig>("my-app", "work").unwrap(), work);
+        assert_eq!(
+            load_profile::<TestConfig>("my-app", "personal").unwrap(),
+            personal
+        );
+        assert!(matches!(
+            load_profile::<TestConfig>("my-app", "missing"),
+            Err(Error::FileNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_load_with_profile() {
+        let home = TempHome::new();
+        let dir = home.config_dir().join("my-app");
+        fs::create_dir_all(dir.join(PROFILES_DIR)).unwrap();
+
+        fs::write(
+            dir.join("config.json"),
+            r#"{ "foo": "base", "bar": true, "baz": 1 }"#,
+        )
+        .unwrap();
+        fs::write(dir.join("profiles/work.json"), r#"{ "foo": "work" }"#).unwrap();
+
+        let base: TestConfig = load_with_profile("my-app", "config.json", None).unwrap();
+        assert_eq!(base.foo, "base");
+
+        let work: TestConfig = load_with_profile("my-app", "config.json", Some("work")).unwrap();
+        assert_eq!(work.foo, "work");
+        assert_eq!(work.baz, 1);
+
+        let err =
+            load_with_profile::<TestConfig>("my-app", "config.json", Some("home")).unwrap_err();
+        assert!(matches!(&err, Error::ProfileNotFound(name) if name == "home"));
+        assert_eq!(err.to_string(), "profile not found: home");
+    }
+
+    #[test]
+    fn test_profile_path_traversal() {
+        let home = TempHome::new();
+        let invalid = |err| matches!(err, Error::InvalidConfig(_));
+
+        crate::init(TestConfig::default(), "my-app", "config.json").unwrap();
+
+        for name in ["../../x", "..", "/tmp/x", "a/b", "..\\x", ""] {
+            assert_eq!(profile_path("my-app", name), None);
+            assert!(invalid(
+                save_profile(&TestConfig::default(), "my-app", name).unwrap_err()
+            ));
+            assert!(invalid(
+                load_profile::<TestConfig>("my-app", name).unwrap_err()
+            ));
+            assert!(invalid(
+                load_with_profile::<TestConfig>("my-app", "config.json", Some(name)).unwrap_err()
+            ));
+        }
+        assert!(!home.dir.path().join("x.json").exists());
+
+        for prefix in ["../..", "..", "/etc", "my-app/.."] {
+            assert_eq!(profiles_dir(prefix), None);
+            assert!(save_profile(&TestConfig::default(), prefix, "work").is_err());
+        }
+
+        assert!(list_profiles("my-app").unwrap().is_empty());
+    }
+}
@@ -0,0 +1,116 @@
+/*!
+  This module contains a `figment` provider
+  reading the config located by this crate.
+*/
+
+use figment::{
+    providers::Serialized,
+    value::{Dict, Map},
+    Metadata, Profile, Provider,
+};
+use std::path::PathBuf;
+
+use crate::value;
+
+/// A [`figment::Provider`] reading the config file located by [`crate::locate_config`],
+/// its format being guessed from its extension
+///
+/// A missing config provides no value, so that the other providers
+/// (e.g. the defaults) are used as-is.
+///
+/// ```no_run
+/// use cli_config::provider::CliConfigProvider;
+/// use figment::{providers::Serialized, Figment};
+///
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct MyConfig {
+///     pub name: String,
+///     pub verbose: bool,
+/// }
+///
+/// let config: MyConfig = Figment::from(Serialized::defaults(MyConfig::default()))
+///     .merge(CliConfigProvider::new("my-app", "config.json"))
+///     .extract()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct CliConfigProvider {
+    prefix: String,
+    filename: String,
+    profile: Profile,
+}
+
+impl CliConfigProvider {
+    /// Provide the config `filename` of `prefix`, under the default profile
+    pub fn new(prefix: &str, filename: &str) -> Self {
+        Self {
+            prefix: prefix.to_string(),
+            filename: filename.to_string(),
+            profile: Profile::Default,
+        }
+    }
+
+    /// Provide the values under the figment `profile` instead
+    pub fn profile(mut self, profile: impl Into<Profile>) -> Self {
+        self.profile = profile.into();
+        self
+    }
+
+    /// Path of the located config file, if any
+    pub fn path(&self) -> Option<PathBuf> {
+        crate::locate_config(&self.prefix, &self.filename)
+    }
+}
+
+impl Provider for CliConfigProvider {
+    fn metadata(&self) -> Metadata {
+        let metadata = Metadata::named("cli-config file");
+
+        match self.path() {
+            Some(path) => metadata.source(path.as_path()),
+            None => metadata,
+        }
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        let Some(path) = self.path() else {
+            return Ok(Map::new());
+        };
+
+        let config = value::load_value(&path)
+            .map_err(|err| figment::Error::from(err.in_file(&path).to_string()))?;
+
+        Serialized::from(config, self.profile.clone()).data()
+    }
+}
+
+#[cfg(all(test, feature = "json", not(windows)))]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TempHome, TestConfig};
+    use figment::Figment;
+
+    #[test]
+    fn test_provider() {
+        let _home = TempHome::new();
+        let defaults = Figment::from(Serialized::defaults(TestConfig::default()));
+
+        let config: TestConfig = defaults
+            .clone()
+            .merge(CliConfigProvider::new("my-app", "config.json"))
+            .extract()
+            .unwrap();
+        assert_eq!(config, TestConfig::default());
+
+        let path = crate::init(TestConfig::default(), "my-app", "config.json").unwrap();
+        std::fs::write(&path, r#"{ "foo": "file", "baz": 1 }"#).unwrap();
+
+        let provider = CliConfigProvider::new("my-app", "config.json");
+        assert_eq!(provider.path(), Some(path));
+
+        let config: TestConfig = defaults.merge(provider).extract().unwrap();
+        assert_eq!(config.foo, "file");
+        assert!(config.bar);
+        assert_eq!(config.baz, 1);
+    }
+}
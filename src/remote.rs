@@ -0,0 +1,170 @@
+/*!
+  This module contains the utilities to load
+  configs hosted at a remote (http/https) URL.
+*/
+
+use serde::de::DeserializeOwned;
+use std::{path::Path, time::Duration};
+
+use crate::{error::Error, format::Format};
+
+/// Timeout applied by [`load_url`] to the whole request
+pub const URL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Fetch the config at `url` and parse it into `T`
+///
+/// The format is detected from the extension of the URL path,
+/// falling back to the `Content-Type` of the response.
+/// Non-2xx responses result in `Error::HttpStatus`, any other failure
+/// (including timeouts) in `Error::Http`.
+pub fn load_url<T>(url: &str) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    load_url_with_timeout(url, URL_TIMEOUT)
+}
+
+/// Same as [`load_url`] with a custom `timeout`
+pub fn load_url_with_timeout<T>(url: &str, timeout: Duration) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let agent = ureq::AgentBuilder::new().timeout(timeout).build();
+
+    let response = match agent.get(url).call() {
+        Ok(response) => response,
+        Err(ureq::Error::Status(status, _)) => {
+            return Err(Error::HttpStatus {
+                url: url.to_string(),
+                status,
+            })
+        }
+        Err(ureq::Error::Transport(err)) => return Err(Error::Http(err.to_string())),
+    };
+
+    let path = url_path(url);
+    let format = match Format::from_path(Path::new(path)) {
+        Some(format) => format,
+        None => from_content_type(response.content_type()).ok_or_else(|| {
+            Error::UnsupportedExtension {
+                extension: Path::new(path)
+                    .extension()
+                    .map(|ext| ext.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                supported: Format::enabled(),
+            }
+        })?,
+    };
+
+    let content = response
+        .into_string()
+        .map_err(|err| Error::Http(err.to_string()))?;
+
+    format.parse(&content)
+}
+
+/// Get the path of `url`, without the query nor the fragment
+fn url_path(url: &str) -> &str {
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let without_scheme = url.split_once("://").map_or(url, |(_, rest)| rest);
+
+    without_scheme
+        .find('/')
+        .map_or("", |idx| &without_scheme[idx..])
+}
+
+/// Get the format matching a (parameter-less) `Content-Type`
+fn from_content_type(content_type: &str) -> Option<Format> {
+    match content_type {
+        "application/json" => Some(Format::Json),
+        "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+            Some(Format::Yaml)
+        }
+        "application/toml" | "text/toml" => Some(Format::Toml),
+        _ => None,
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestConfig;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    const BODY: &str = r#"{ "foo": "remote", "bar": false, "baz": 1 }"#;
+
+    /// Answer a single request with `status` and `body`, returning the server address
+    fn serve(status: &'static str, content_type: &'static str, body: &'static str) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+            // drain the request headers
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 2 {
+                line.clear();
+            }
+
+            write!(
+                stream,
+                "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .unwrap();
+        });
+
+        format!("http://{addr}")
+    }
+
+    #[test]
+    fn test_url_path() {
+        assert_eq!(url_path("https://example.com"), "");
+        assert_eq!(
+            url_path("https://example.com/app/config.json?v=1#top"),
+            "/app/config.json"
+        );
+    }
+
+    #[test]
+    fn test_load_url() {
+        let url = serve("200 OK", "text/plain", BODY);
+
+        let config: TestConfig = load_url(&format!("{url}/config.json")).unwrap();
+        assert_eq!(config.foo, "remote");
+        assert!(!config.bar);
+    }
+
+    #[test]
+    fn test_load_url_content_type() {
+        let url = serve("200 OK", "application/json; charset=utf-8", BODY);
+
+        let config: TestConfig = load_url(&format!("{url}/config")).unwrap();
+        assert_eq!(config.baz, 1);
+    }
+
+    #[test]
+    fn test_load_url_status() {
+        let url = serve("404 Not Found", "text/plain", "");
+
+        let err = load_url::<TestConfig>(&format!("{url}/config.json")).unwrap_err();
+        assert!(matches!(err, Error::HttpStatus { status: 404, .. }));
+        assert!(err.to_string().contains("404"));
+    }
+
+    #[test]
+    fn test_load_url_timeout() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/config.json", listener.local_addr().unwrap());
+
+        // the connection is accepted by the OS but never answered
+        let err =
+            load_url_with_timeout::<TestConfig>(&url, Duration::from_millis(100)).unwrap_err();
+        assert!(matches!(err, Error::Http(_)));
+        drop(listener);
+    }
+}
@@ -0,0 +1,156 @@
+/*!
+  This module contains the utilities to generate
+  the JSON schema of a config via [`schemars`]
+  and to validate a config against it.
+*/
+
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{error::Error, value};
+
+/// Get the JSON schema of `T`
+pub fn json_schema<T>() -> Value
+where
+    T: JsonSchema,
+{
+    schemars::schema_for!(T).to_value()
+}
+
+/// Write the default config `{base_name}.json` and its JSON schema
+/// `{base_name}.schema.json` into `dir` (created if needed), e.g. from a build step
+///
+/// Both files are pretty-printed JSON, their paths are returned in this order.
+pub fn generate_artifacts<T>(dir: impl AsRef<Path>, base_name: &str) -> crate::Result<Vec<PathBuf>>
+where
+    T: Serialize + JsonSchema + Default,
+{
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+
+    let config_path = dir.join(format!("{base_name}.json"));
+    let schema_path = dir.join(format!("{base_name}.schema.json"));
+
+    std::fs::write(&config_path, serde_json::to_string_pretty(&T::default())?)?;
+    std::fs::write(
+        &schema_path,
+        serde_json::to_string_pretty(&json_schema::<T>())?,
+    )?;
+
+    Ok(vec![config_path, schema_path])
+}
+
+/// A violation of the JSON schema reported by [`validate_all`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SchemaError {
+    /// JSON pointer of the invalid value (e.g. `/servers/0/port`),
+    /// empty for the document itself
+    pub instance_path: String,
+    /// Human readable description
+    pub message: String,
+}
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.instance_path.as_str() {
+            "" => f.write_str(&self.message),
+            path => write!(f, "{path}: {}", self.message),
+        }
+    }
+}
+
+/// Check the config at `path` against the JSON schema of `T` and return
+/// every violation found, unlike deserializing it which stops at the first
+/// error (e.g. for a `config check` command)
+///
+/// The format is detected from the extension, an empty list means that
+/// the config is valid. A config which cannot be parsed is an error.
+pub fn validate_all<T>(path: impl AsRef<Path>) -> crate::Result<Vec<SchemaError>>
+where
+    T: JsonSchema,
+{
+    let config = value::load_value(path)?;
+    let validator = jsonschema::validator_for(&json_schema::<T>())
+        .map_err(|err| Error::validation(format!("invalid schema: {err}")))?;
+
+    Ok(validator
+        .iter_errors(&config)
+        .map(|err| SchemaError {
+            instance_path: err.instance_path().to_string(),
+            message: err.to_string(),
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[derive(Debug, PartialEq, Serialize, serde::Deserialize, JsonSchema)]
+    struct SchemaConfig {
+        name: String,
+        port: u16,
+    }
+
+    impl Default for SchemaConfig {
+        fn default() -> Self {
+            Self {
+                name: "app".to_string(),
+                port: 8080,
+            }
+        }
+    }
+
+    #[test]
+    fn test_generate_artifacts() {
+        let dir = TempDir::new("test_config").unwrap();
+        let out = dir.path().join("generated");
+
+        let paths = generate_artifacts::<SchemaConfig>(&out, "config").unwrap();
+        assert_eq!(
+            paths,
+            vec![out.join("config.json"), out.join("config.schema.json")]
+        );
+
+        let config: SchemaConfig =
+            serde_json::from_str(&std::fs::read_to_string(&paths[0]).unwrap()).unwrap();
+        assert_eq!(config, SchemaConfig::default());
+
+        let schema: Value =
+            serde_json::from_str(&std::fs::read_to_string(&paths[1]).unwrap()).unwrap();
+        assert_eq!(schema, json_schema::<SchemaConfig>());
+        assert_eq!(schema["title"], "SchemaConfig");
+        assert_eq!(schema["properties"]["port"]["type"], "integer");
+        assert_eq!(schema["required"], serde_json::json!(["name", "port"]));
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_validate_all() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+
+        std::fs::write(&path, r#"{ "name": "app", "port": 8080 }"#).unwrap();
+        assert!(validate_all::<SchemaConfig>(&path).unwrap().is_empty());
+
+        std::fs::write(&path, r#"{ "name": 42, "port": 70000 }"#).unwrap();
+        let mut errors = validate_all::<SchemaConfig>(&path).unwrap();
+        errors.sort_by(|a, b| a.instance_path.cmp(&b.instance_path));
+
+        assert_eq!(errors.len(), 2, "{errors:?}");
+        assert_eq!(errors[0].instance_path, "/name");
+        assert!(errors[0].message.contains("not of type"), "{}", errors[0]);
+        assert_eq!(errors[1].instance_path, "/port");
+        assert!(
+            errors[1].to_string().starts_with("/port: 70000"),
+            "{}",
+            errors[1]
+        );
+    }
+}
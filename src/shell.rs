@@ -0,0 +1,197 @@
+/*!
+  This module contains the utilities to export
+  a config as shell variable assignments.
+*/
+
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::error::Error;
+
+/// A variable exported by [`to_shell_env`]
+struct Assignment {
+    name: String,
+    /// Dotted path of the key holding the value
+    key: String,
+    value: String,
+}
+
+/// Flatten `config` into `PREFIX_NESTED_KEY='value'` lines, ready for `eval "$(...)"`,
+/// also available as [`crate::fs::File::to_shell_env`].
+///
+/// - keys are joined with `_`, uppercased and every character that is not
+///   valid in a variable name is replaced by `_`
+/// - arrays are exported as indexed variables (`PREFIX_LIST_0`, `PREFIX_LIST_1`, ...)
+/// - `null` values are skipped
+/// - values are always single-quoted
+///
+/// Keys ending up with the same variable name (e.g. `a.b` and `a_b`) fail
+/// with `Error::ShellVarCollision`.
+pub fn to_shell_env<T>(config: &T, prefix: &str) -> crate::Result<String>
+where
+    T: Serialize + ?Sized,
+{
+    let value = serde_json::to_value(config)?;
+    let mut assignments = Vec::new();
+
+    flatten(&value, &sanitize(prefix), "", &mut assignments);
+
+    let mut keys = HashMap::new();
+    let mut output = String::new();
+
+    for assignment in &assignments {
+        if let Some(first) = keys.insert(assignment.name.as_str(), assignment.key.as_str()) {
+            return Err(Error::ShellVarCollision {
+                name: assignment.name.clone(),
+                first: first.to_string(),
+                second: assignment.key.clone(),
+            });
+        }
+
+        push_assignment(&mut output, &assignment.name, &assignment.value);
+    }
+
+    Ok(output)
+}
+
+fn flatten(value: &Value, name: &str, key: &str, assignments: &mut Vec<Assignment>) {
+    let child = |child: &str| {
+        let name = match name {
+            "" => sanitize(child),
+            _ => format!("{name}_{}", sanitize(child)),
+        };
+        let key = match key {
+            "" => child.to_string(),
+            _ => format!("{key}.{child}"),
+        };
+
+        (name, key)
+    };
+
+    let mut push = |value: String| {
+        assignments.push(Assignment {
+            name: name.to_string(),
+            key: key.to_string(),
+            value,
+        })
+    };
+
+    match value {
+        Value::Null => {}
+        Value::Object(map) => {
+            for (child_key, value) in map {
+                let (name, key) = child(child_key);
+                flatten(value, &name, &key, assignments);
+            }
+        }
+        Value::Array(items) => {
+            for (idx, value) in items.iter().enumerate() {
+                let (name, key) = child(&idx.to_string());
+                flatten(value, &name, &key, assignments);
+            }
+        }
+        Value::String(s) => push(s.clone()),
+        scalar => push(scalar.to_string()),
+    }
+}
+
+fn push_assignment(output: &mut String, name: &str, value: &str) {
+    output.push_str(name);
+    output.push('=');
+    output.push_str(&quote(value));
+    output.push('\n');
+}
+
+/// Turn `key` into a valid (uppercase) shell variable name fragment
+fn sanitize(key: &str) -> String {
+    key.chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Single-quote `value` for POSIX shells
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Server {
+        host: String,
+        ports: Vec<u16>,
+    }
+
+    #[derive(Serialize)]
+    struct ShellConfig {
+        name: String,
+        debug: bool,
+        token: Option<String>,
+        server: Server,
+    }
+
+    #[test]
+    fn test_to_shell_env() {
+        let config = ShellConfig {
+            name: "it's mine".to_string(),
+            debug: true,
+            token: None,
+            server: Server {
+                host: "localhost".to_string(),
+                ports: vec![80, 443],
+            },
+        };
+
+        let env = to_shell_env(&config, "my-app").unwrap();
+
+        assert_eq!(
+            env,
+            "MY_APP_DEBUG='true'\n\
+             MY_APP_NAME='it'\\''s mine'\n\
+             MY_APP_SERVER_HOST='localhost'\n\
+             MY_APP_SERVER_PORTS_0='80'\n\
+             MY_APP_SERVER_PORTS_1='443'\n"
+        );
+    }
+
+    #[test]
+    fn test_to_shell_env_without_prefix() {
+        let env = to_shell_env(&serde_json::json!({ "a": { "b": "$HOME" } }), "").unwrap();
+
+        assert_eq!(env, "A_B='$HOME'\n");
+    }
+
+    #[test]
+    fn test_to_shell_env_collision() {
+        let config = serde_json::json!({ "a": { "b": 1 }, "a_b": 2 });
+        let err = to_shell_env(&config, "app").unwrap_err();
+
+        assert!(matches!(
+            &err,
+            Error::ShellVarCollision { name, first, second }
+                if name == "APP_A_B" && first == "a.b" && second == "a_b"
+        ));
+        assert_eq!(err.kind(), "shell_var_collision");
+
+        assert!(to_shell_env(&serde_json::json!({ "my-key": 1, "my_key": 2 }), "").is_err());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_file_to_shell_env() {
+        use crate::fs::File;
+
+        let config = crate::test_utils::TestConfig::default();
+
+        assert_eq!(
+            config.to_shell_env("app").unwrap(),
+            "APP_BAR='true'\nAPP_BAZ='42'\nAPP_FOO='foo'\n"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+/*!
+  This module contains a builder chaining config sources,
+  similar to the one of the `config` crate.
+*/
+
+use serde::Serialize;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+
+use crate::{merge::Merge, value};
+
+/// A source layered by [`Sources`]
+#[derive(Debug)]
+enum Layer {
+    Defaults(serde_json::Result<Value>),
+    File { path: PathBuf, required: bool },
+    Env(String),
+}
+
+/// Config sources merged in the order they are added, a later source
+/// taking precedence over the previous ones
+///
+/// The sources are only read by [`Sources::build`], which deserializes
+/// the merged config once at the end.
+///
+/// ```no_run
+/// use cli_config::{merge::Merge, sources::Sources};
+///
+/// #[derive(Default, serde::Serialize, serde::Deserialize)]
+/// struct MyConfig {
+///     pub name: String,
+///     pub verbose: bool,
+/// }
+///
+/// impl Merge for MyConfig {}
+///
+/// let config: MyConfig = Sources::new()
+///     .defaults(MyConfig::default())
+///     .file("config.json")
+///     .env("MYAPP")
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct Sources {
+    layers: Vec<Layer>,
+}
+
+impl Sources {
+    /// Start without any source
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add `defaults`, usually `T::default()`
+    pub fn defaults(mut self, defaults: impl Serialize) -> Self {
+        self.layers
+            .push(Layer::Defaults(serde_json::to_value(defaults)));
+        self
+    }
+
+    /// Add the config file at `path`, its format being guessed from its extension
+    ///
+    /// [`Sources::build`] fails if the file doesn't exist.
+    pub fn file(mut self, path: impl AsRef<Path>) -> Self {
+        self.layers.push(Layer::File {
+            path: path.as_ref().to_path_buf(),
+            required: true,
+        });
+        self
+    }
+
+    /// Same as [`Sources::file`] but a missing file is skipped
+    pub fn optional_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.layers.push(Layer::File {
+            path: path.as_ref().to_path_buf(),
+            required: false,
+        });
+        self
+    }
+
+    /// Add the environment variables `{prefix}_{PATH}`, e.g. `MYAPP_SERVER_PORT`
+    /// for `server.port`
+    ///
+    /// Only the fields set by the previous sources can be overridden, variables
+    /// are read as strings falling back to their JSON value for non-string fields
+    /// (see [`crate::merge::load_with_provenance`]).
+    pub fn env(mut self, prefix: &str) -> Self {
+        self.layers.push(Layer::Env(prefix.to_string()));
+        self
+    }
+
+    /// Merge the sources, using [`Merge::merge_value`], and deserialize `T`
+    pub fn build<T>(self) -> crate::Result<T>
+    where
+        T: Merge,
+    {
+        let mut config = Value::Object(Default::default());
+
+        for layer in self.layers {
+            let layer = match layer {
+                Layer::Defaults(defaults) => defaults?,
+                Layer::File { path, required } => {
+                    if !required && !path.exists() {
+                        continue;
+                    }
+
+                    value::load_value(&path).map_err(|err| err.in_file(&path))?
+                }
+                Layer::Env(prefix) => crate::merge::env_layer(&config, &prefix),
+            };
+
+            T::merge_value(&mut config, layer);
+        }
+
+        value::from_value(config)
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{lock_env, TestConfig};
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_sources() {
+        let _env = lock_env();
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, r#"{ "foo": "file", "baz": 1 }"#).unwrap();
+
+        std::env::set_var("SOURCES_TEST_BAZ", "3");
+
+        let config: TestConfig = Sources::new()
+            .defaults(TestConfig::default())
+            .file(&path)
+            .optional_file(dir.path().join("missing.json"))
+            .env("SOURCES_TEST")
+            .build()
+            .unwrap();
+
+        std::env::remove_var("SOURCES_TEST_BAZ");
+
+        assert_eq!(config.foo, "file");
+        assert!(config.bar);
+        assert_eq!(config.baz, 3);
+
+        assert!(Sources::new()
+            .defaults(TestConfig::default())
+            .file(dir.path().join("missing.json"))
+            .build::<TestConfig>()
+            .is_err());
+    }
+}
@@ -0,0 +1,234 @@
+/*!
+  This module contains the [`Store`] abstraction, to write the
+  application logic independently of where the config is persisted.
+*/
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::path::PathBuf;
+
+use crate::{error::Error, format::Format};
+
+/// Loads and saves a `T`, e.g. swap the [`FileStore`] for an in-memory mock in tests
+pub trait Store<T> {
+    /// Load the stored value
+    fn get(&self) -> crate::Result<T>;
+
+    /// Replace the stored value with `value`
+    fn put(&self, value: &T) -> crate::Result<()>;
+}
+
+/// A [`Store`] backed by the config file discovered with [`crate::locate_config`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileStore {
+    prefix: String,
+    filename: String,
+    format: Format,
+}
+
+impl FileStore {
+    /// Create a store for `{prefix}/{filename}` (de)serialized using `format`
+    pub fn new(prefix: impl Into<String>, filename: impl Into<String>, format: Format) -> Self {
+        Self {
+            prefix: prefix.into(),
+            filename: filename.into(),
+            format,
+        }
+    }
+
+    /// Location of the config file, if it exists
+    pub fn path(&self) -> Option<PathBuf> {
+        crate::locate_config(&self.prefix, &self.filename)
+    }
+}
+
+impl<T> Store<T> for FileStore
+where
+    T: Serialize + DeserializeOwned,
+{
+    fn get(&self) -> crate::Result<T> {
+        let path = self.path().ok_or(Error::FileNotFound)?;
+        let content = crate::fs::read_config(&path)?;
+
+        self.format.parse(&content).map_err(|err| err.in_file(path))
+    }
+
+    fn put(&self, value: &T) -> crate::Result<()> {
+        let path = match self.path() {
+            Some(path) => path,
+            None => crate::get_new_config_path(&self.prefix, &self.filename)?
+                .ok_or(Error::Custom("Could not create file"))?,
+        };
+
+        std::fs::write(path, self.format.serialize(value)?)?;
+
+        Ok(())
+    }
+}
+
+/// A handle on the config `{prefix}/{filename}`, (de)serialized using `format`,
+/// keeping the last value loaded with [`ConfigManager::reload`]
+#[derive(Debug, Clone)]
+pub struct ConfigManager<T> {
+    store: FileStore,
+    value: Option<T>,
+}
+
+impl<T> ConfigManager<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    /// Create a manager for `{prefix}/{filename}` (de)serialized using `format`
+    pub fn new(prefix: impl Into<String>, filename: impl Into<String>, format: Format) -> Self {
+        Self {
+            store: FileStore::new(prefix, filename, format),
+            value: None,
+        }
+    }
+
+    /// Location of the config file, if it exists
+    pub fn path(&self) -> Option<PathBuf> {
+        self.store.path()
+    }
+
+    /// Whether the config file exists
+    pub fn exists(&self) -> bool {
+        self.path().is_some()
+    }
+
+    /// Load the config
+    ///
+    /// Returns `Error::FileNotFound` if the config file doesn't exist.
+    pub fn load(&self) -> crate::Result<T> {
+        self.store.get()
+    }
+
+    /// Load the config, falling back to `T::default()` when the file doesn't exist
+    pub fn load_or_default(&self) -> crate::Result<T>
+    where
+        T: Default,
+    {
+        match self.load() {
+            Err(Error::FileNotFound) => Ok(T::default()),
+            result => result,
+        }
+    }
+
+    /// Write `value` into the config file, creating it if needed
+    pub fn save(&self, value: &T) -> crate::Result<()> {
+        self.store.put(value)
+    }
+
+    /// Load the config again and keep it, see [`ConfigManager::value`]
+    pub fn reload(&mut self) -> crate::Result<&T> {
+        let value = self.load()?;
+
+        Ok(self.value.insert(value))
+    }
+
+    /// The value kept by the last [`ConfigManager::reload`]
+    pub fn value(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// Remove the config file (and forget the kept value),
+    /// returning whether there was one
+    pub fn delete(&mut self) -> crate::Result<bool> {
+        self.value = None;
+
+        match self.path() {
+            Some(path) => {
+                std::fs::remove_file(path)?;
+                Ok(true)
+            }
+            None => Ok(false),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TempHome, TestConfig};
+    use std::cell::RefCell;
+
+    /// Some application logic only knowing about `Store`
+    fn increment(store: &impl Store<TestConfig>) -> crate::Result<u32> {
+        let mut config = store.get()?;
+        config.baz += 1;
+        store.put(&config)?;
+
+        Ok(config.baz)
+    }
+
+    #[derive(Default)]
+    struct MemoryStore(RefCell<TestConfig>);
+
+    impl Store<TestConfig> for MemoryStore {
+        fn get(&self) -> crate::Result<TestConfig> {
+            Ok(self.0.borrow().clone())
+        }
+
+        fn put(&self, value: &TestConfig) -> crate::Result<()> {
+            *self.0.borrow_mut() = value.clone();
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_mock_store() {
+        let store = MemoryStore::default();
+
+        assert_eq!(increment(&store).unwrap(), 43);
+        assert_eq!(increment(&store).unwrap(), 44);
+        assert_eq!(store.0.borrow().baz, 44);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_file_store() {
+        let _home = TempHome::new();
+        let store = FileStore::new("my-app", "config.json", Format::Json);
+
+        assert!(matches!(
+            Store::<TestConfig>::get(&store),
+            Err(Error::FileNotFound)
+        ));
+
+        store.put(&TestConfig::default()).unwrap();
+        assert_eq!(increment(&store).unwrap(), 43);
+
+        let config: TestConfig = store.get().unwrap();
+        assert_eq!(config.baz, 43);
+        assert!(store.path().unwrap().ends_with("my-app/config.json"));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn test_config_manager_lifecycle() {
+        let _home = TempHome::new();
+        let mut manager = ConfigManager::<TestConfig>::new("my-app", "config.json", Format::Json);
+
+        assert!(!manager.exists());
+        assert!(manager.value().is_none());
+        assert!(matches!(manager.reload(), Err(Error::FileNotFound)));
+
+        let mut config = manager.load_or_default().unwrap();
+        assert_eq!(config, TestConfig::default());
+
+        config.baz += 1;
+        manager.save(&config).unwrap();
+
+        assert!(manager.exists());
+        assert!(manager.path().unwrap().ends_with("my-app/config.json"));
+        assert_eq!(manager.reload().unwrap(), &config);
+        assert_eq!(manager.value(), Some(&config));
+        assert_eq!(manager.load().unwrap().baz, 43);
+
+        assert!(manager.delete().unwrap());
+        assert!(!manager.exists());
+        assert!(manager.value().is_none());
+        assert!(!manager.delete().unwrap());
+    }
+}
@@ -0,0 +1,94 @@
+/*!
+  Shared helpers for the unit tests
+*/
+#![allow(dead_code)]
+
+use serde::{Deserialize, Serialize};
+use std::ffi::OsString;
+#[cfg(feature = "json")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Mutex, MutexGuard};
+use tempdir::TempDir;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TestConfig {
+    pub foo: String,
+    pub bar: bool,
+    pub baz: u32,
+}
+
+impl Default for TestConfig {
+    fn default() -> Self {
+        Self {
+            foo: "foo".to_string(),
+            bar: true,
+            baz: 42,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl crate::fs::File for TestConfig {
+    fn load(path: impl AsRef<Path>) -> crate::Result<Self> {
+        <Self as crate::fs::JSONFile>::load(path)
+    }
+
+    fn write_path(&self, path: &Path) -> crate::Result<()> {
+        crate::fs::JSONFile::write(self, path)
+    }
+}
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+/// Serialize the tests that read or mutate the process environment
+pub fn lock_env() -> MutexGuard<'static, ()> {
+    ENV_LOCK.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// An isolated `$HOME` (with `$XDG_CONFIG_HOME` set to `$HOME/.config`)
+/// which is restored on drop.
+pub struct TempHome {
+    pub dir: TempDir,
+    previous: Vec<(&'static str, Option<OsString>)>,
+    _env: MutexGuard<'static, ()>,
+}
+
+impl TempHome {
+    pub fn new() -> Self {
+        let env = lock_env();
+        let dir = TempDir::new("test_home").unwrap();
+        let previous = ["HOME", "XDG_CONFIG_HOME"]
+            .into_iter()
+            .map(|key| (key, std::env::var_os(key)))
+            .collect();
+
+        std::env::set_var("HOME", dir.path());
+        std::env::set_var("XDG_CONFIG_HOME", dir.path().join(".config"));
+        crate::reset_config_cache();
+
+        Self {
+            dir,
+            previous,
+            _env: env,
+        }
+    }
+
+    /// `$XDG_CONFIG_HOME`
+    pub fn config_dir(&self) -> PathBuf {
+        self.dir.path().join(".config")
+    }
+}
+
+impl Drop for TempHome {
+    fn drop(&mut self) {
+        for (key, value) in &self.previous {
+            match value {
+                Some(value) => std::env::set_var(key, value),
+                None => std::env::remove_var(key),
+            }
+        }
+
+        crate::reset_config_cache();
+    }
+}
@@ -0,0 +1,889 @@
+/*!
+  This module contains helpers working on the generic
+  [`Value`] representation of a config, regardless of its format.
+*/
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::error::Error;
+
+/// Load the file at `path` into a [`Value`], the format is detected from its extension
+pub fn load_value(path: impl AsRef<Path>) -> crate::Result<Value> {
+    crate::format::load_auto(path)
+}
+
+/// Deserialize `T` from a [`Value`]
+pub fn from_value<T>(value: Value) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    crate::fs::deserialize(value, Error::JSON)
+}
+
+/// Remove every `null` entry from the objects in `value`, recursively
+///
+/// Array items are kept so that positions are preserved.
+pub fn prune_nulls(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|_, value| !value.is_null());
+            map.values_mut().for_each(prune_nulls);
+        }
+        Value::Array(items) => items.iter_mut().for_each(prune_nulls),
+        _ => {}
+    }
+}
+
+/// Sort the keys of every object in `value`, recursively
+///
+/// Objects only keep their keys sorted by themselves while the `preserve_order`
+/// feature of `serde_json` is off, which any crate of the build can turn on.
+pub fn sort_keys(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            let mut sorted: std::collections::BTreeMap<_, _> =
+                std::mem::take(map).into_iter().collect();
+            sorted.values_mut().for_each(sort_keys);
+            map.extend(sorted);
+        }
+        Value::Array(items) => items.iter_mut().for_each(sort_keys),
+        _ => {}
+    }
+}
+
+/// Round every float in `value` to `decimals` decimal places, recursively
+///
+/// Integers are left untouched. Rounding loses precision by design.
+pub fn round_floats(value: &mut Value, decimals: u32) {
+    match value {
+        Value::Number(number) if number.is_f64() => {
+            let factor = 10f64.powi(decimals as i32);
+            let rounded = number
+                .as_f64()
+                .map(|float| (float * factor).round() / factor)
+                .and_then(serde_json::Number::from_f64);
+
+            if let Some(rounded) = rounded {
+                *number = rounded;
+            }
+        }
+        Value::Object(map) => map
+            .values_mut()
+            .for_each(|value| round_floats(value, decimals)),
+        Value::Array(items) => items
+            .iter_mut()
+            .for_each(|value| round_floats(value, decimals)),
+        _ => {}
+    }
+}
+
+/// Get the value at `dotted_key` (e.g. `server.port`) of `config`
+pub fn get_path<'a>(config: &'a Value, dotted_key: &str) -> Option<&'a Value> {
+    dotted_key
+        .split('.')
+        .try_fold(config, |value, key| value.as_object()?.get(key))
+}
+
+/// Get the value at `dotted_key`, failing with `Error::KeyNotFound`
+fn get_required<'a>(config: &'a Value, dotted_key: &str) -> crate::Result<&'a Value> {
+    get_path(config, dotted_key).ok_or_else(|| Error::KeyNotFound(dotted_key.to_string()))
+}
+
+/// `Error::InvalidType` for `value` found at `dotted_key`
+fn invalid_type(dotted_key: &str, expected: &'static str, value: &Value) -> Error {
+    let found = match value {
+        Value::Null => "null",
+        Value::Bool(_) => "a boolean",
+        Value::Number(_) => "a number",
+        Value::String(_) => "a string",
+        Value::Array(_) => "an array",
+        Value::Object(_) => "an object",
+    };
+
+    Error::InvalidType {
+        key: dotted_key.to_string(),
+        expected,
+        found,
+    }
+}
+
+/// Get the string at `dotted_key` of `config`
+///
+/// Fails with `Error::KeyNotFound` when there is no such key and with
+/// `Error::InvalidType` when it's not a string (values are never converted).
+pub fn get_string<'a>(config: &'a Value, dotted_key: &str) -> crate::Result<&'a str> {
+    let value = get_required(config, dotted_key)?;
+
+    value
+        .as_str()
+        .ok_or_else(|| invalid_type(dotted_key, "a string", value))
+}
+
+/// Get the unsigned integer at `dotted_key` of `config`, see [`get_string`]
+pub fn get_u64(config: &Value, dotted_key: &str) -> crate::Result<u64> {
+    let value = get_required(config, dotted_key)?;
+
+    value
+        .as_u64()
+        .ok_or_else(|| invalid_type(dotted_key, "an unsigned integer", value))
+}
+
+/// Get the boolean at `dotted_key` of `config`, see [`get_string`]
+pub fn get_bool(config: &Value, dotted_key: &str) -> crate::Result<bool> {
+    let value = get_required(config, dotted_key)?;
+
+    value
+        .as_bool()
+        .ok_or_else(|| invalid_type(dotted_key, "a boolean", value))
+}
+
+/// Set the key at `dotted_key` (e.g. `server.port`) of the config at `path`
+/// to `value` and write it back using `format`, without knowing its type.
+///
+/// Missing intermediate objects are created, every other key is kept.
+/// Comments and formatting are not preserved.
+pub fn patch_field(
+    path: impl AsRef<Path>,
+    dotted_key: &str,
+    value: Value,
+    format: crate::format::Format,
+) -> crate::Result<()> {
+    let path = path.as_ref();
+    let mut config: Value = format.parse(&crate::fs::read_config(path)?)?;
+
+    set_path(&mut config, dotted_key, value)?;
+    std::fs::write(path, serialize_value(&config, format)?)?;
+
+    Ok(())
+}
+
+/// Serialize the generic `config` using `format`
+pub(crate) fn serialize_value(
+    config: &Value,
+    format: crate::format::Format,
+) -> crate::Result<String> {
+    // the plain values must come before the tables, which `toml::Value` takes care of,
+    // and TOML has no `null`: the `None` fields are omitted as `toml` does for structs
+    #[cfg(feature = "toml")]
+    if format == crate::format::Format::Toml {
+        let mut config = config.clone();
+        prune_nulls(&mut config);
+
+        return format.serialize(&toml::Value::try_from(config)?);
+    }
+
+    format.serialize(config)
+}
+
+/// Replace the values at `dotted_keys` of `config` with `"***"`,
+/// the missing keys are ignored
+pub(crate) fn redact(config: &mut Value, dotted_keys: &[&str]) {
+    for dotted_key in dotted_keys {
+        let target = dotted_key.split('.').try_fold(&mut *config, |value, key| {
+            value.as_object_mut()?.get_mut(key)
+        });
+
+        if let Some(target) = target {
+            *target = Value::String(REDACTED.to_string());
+        }
+    }
+}
+
+/// Placeholder of the redacted values
+pub(crate) const REDACTED: &str = "***";
+
+/// Set the key at `dotted_key` of `config` to `value`
+pub(crate) fn set_path(config: &mut Value, dotted_key: &str, value: Value) -> crate::Result<()> {
+    if dotted_key.split('.').any(str::is_empty) {
+        return Err(Error::InvalidConfig("invalid key"));
+    }
+
+    let mut target = config;
+
+    for key in dotted_key.split('.') {
+        let Value::Object(map) = target else {
+            return Err(Error::InvalidConfig(
+                "cannot set a key inside a non-object value",
+            ));
+        };
+
+        target = map
+            .entry(key)
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+
+    *target = value;
+
+    Ok(())
+}
+
+/// Load the config at `path` into `T` refusing documents nested deeper than
+/// `max_depth` objects/arrays, e.g. when the config comes from an untrusted source.
+///
+/// The JSON and YAML parsers have their own (fixed) recursion limit, so these
+/// documents are parsed before checking their depth. The TOML parser has none:
+/// a TOML document is scanned first (see [`check_raw_depth`]) and never parsed
+/// when it may be too deep.
+pub fn load_limited_depth<T>(path: impl AsRef<Path>, max_depth: usize) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let path = path.as_ref();
+    let format = crate::format::Format::for_path(path)?;
+    let content = crate::fs::read_config(path)?;
+
+    check_raw_depth(&content, format, max_depth)?;
+    let config: Value = format.parse(&content)?;
+
+    if depth(&config) > max_depth {
+        return Err(Error::InvalidConfig("config nesting too deep"));
+    }
+
+    from_value(config)
+}
+
+/// Refuse the `content` of a `format` document which may be nested deeper
+/// than `max_depth`, before it's parsed
+///
+/// Only TOML is checked, the other parsers limit their recursion themselves.
+pub(crate) fn check_raw_depth(
+    content: &str,
+    format: crate::format::Format,
+    max_depth: usize,
+) -> crate::Result<()> {
+    match format {
+        crate::format::Format::Toml if toml_depth(content) > max_depth => {
+            Err(Error::InvalidConfig("config nesting too deep"))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Upper bound of the nesting depth of the TOML `input`, computed without parsing it
+///
+/// Arrays, inline tables and each segment of a dotted key (table headers included)
+/// count as one level, strings and comments are skipped. The dot of a float counts
+/// as well, so the result may exceed the actual depth.
+fn toml_depth(input: &str) -> usize {
+    let input = input.as_bytes();
+    let mut max = 0;
+    // depth before each open bracket
+    let mut stack = Vec::new();
+    // depth of the last table header, where the following keys start,
+    // the root table being the first level
+    let mut header = 1;
+    let mut in_header = false;
+    let mut line_start = true;
+    let mut current = header;
+    let mut idx = 0;
+
+    while idx < input.len() {
+        let byte = input[idx];
+        idx += 1;
+
+        match byte {
+            b'\n' => {
+                if stack.is_empty() {
+                    current = header;
+                }
+                line_start = true;
+                continue;
+            }
+            b' ' | b'\t' | b'\r' => continue,
+            b'#' => idx = skip_line(input, idx),
+            b'"' | b'\'' => idx = skip_string(input, idx - 1),
+            b'[' | b'{' => {
+                if stack.is_empty() && line_start && byte == b'[' {
+                    in_header = true;
+                    header = 0;
+                }
+
+                stack.push(current);
+                current += 1;
+            }
+            b']' | b'}' => {
+                current = stack.pop().unwrap_or(header);
+
+                if stack.is_empty() && in_header {
+                    in_header = false;
+                    current = header;
+                }
+            }
+            b'.' => current += 1,
+            b',' => current = stack.last().map_or(header, |depth| depth + 1),
+            _ => {}
+        }
+
+        line_start = false;
+        max = max.max(current);
+
+        if in_header {
+            header = header.max(current);
+        }
+    }
+
+    max
+}
+
+/// Index of the end of the line starting at `idx`
+fn skip_line(input: &[u8], idx: usize) -> usize {
+    input[idx..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(input.len(), |end| idx + end)
+}
+
+/// Index right after the TOML string opening at `idx`, whether basic (`"`),
+/// literal (`'`) or one of their multi-line variants
+fn skip_string(input: &[u8], idx: usize) -> usize {
+    let quote = input[idx];
+    // only basic strings have escapes
+    let escapes = quote == b'"';
+
+    if input[idx..].starts_with(&[quote; 3]) {
+        let mut idx = idx + 3;
+
+        while idx < input.len() {
+            if escapes && input[idx] == b'\\' {
+                idx += 2;
+            } else if input[idx..].starts_with(&[quote; 3]) {
+                idx += 3;
+                // up to two quotes may end the content
+                let end = (idx + 2).min(input.len());
+                while idx < end && input[idx] == quote {
+                    idx += 1;
+                }
+                return idx;
+            } else {
+                idx += 1;
+            }
+        }
+
+        return input.len();
+    }
+
+    let mut idx = idx + 1;
+
+    while idx < input.len() {
+        match input[idx] {
+            b'\\' if escapes => idx += 2,
+            b'\n' => return idx,
+            byte if byte == quote => return idx + 1,
+            _ => idx += 1,
+        }
+    }
+
+    input.len()
+}
+
+/// Nesting depth of `value`, computed without recursion (a scalar has a depth of 0)
+pub(crate) fn depth(value: &Value) -> usize {
+    let mut max = 0;
+    let mut stack = vec![(value, 0)];
+
+    while let Some((value, level)) = stack.pop() {
+        match value {
+            Value::Object(map) => stack.extend(map.values().map(|child| (child, level + 1))),
+            Value::Array(items) => stack.extend(items.iter().map(|child| (child, level + 1))),
+            _ => continue,
+        }
+
+        max = max.max(level + 1);
+    }
+
+    max
+}
+
+/// Load only the top-level `section` of the config at `path` into `T`
+///
+/// Returns `Error::SectionNotFound` if the config has no such section.
+pub fn load_section<T>(path: impl AsRef<Path>, section: &str) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    match load_value(path)? {
+        Value::Object(mut map) => match map.remove(section) {
+            Some(value) => from_value(value),
+            None => Err(Error::SectionNotFound(section.to_string())),
+        },
+        _ => Err(Error::SectionNotFound(section.to_string())),
+    }
+}
+
+/// Load the config at `path` into `T` matching its top-level keys
+/// case-insensitively against the fields of `T` (e.g. `Port` sets `port`).
+///
+/// Nested keys are left untouched. When several keys only differ by case,
+/// the one matching the field name exactly wins and the others are ignored.
+pub fn load_case_insensitive<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: DeserializeOwned,
+{
+    let mut config = load_value(path)?;
+
+    if let Value::Object(map) = &mut config {
+        for field in struct_fields::<T>() {
+            if map.contains_key(*field) {
+                continue;
+            }
+
+            let key = map
+                .keys()
+                .find(|key| key.eq_ignore_ascii_case(field))
+                .cloned();
+
+            if let Some(value) = key.and_then(|key| map.remove(&key)) {
+                map.insert(field.to_string(), value);
+            }
+        }
+    }
+
+    from_value(config)
+}
+
+/// A deprecated (renamed) key found by [`load_with_aliases`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeprecatedKey {
+    /// The old name, as found in the config
+    pub old: String,
+    /// The name replacing it
+    pub new: String,
+    /// Whether the new key was also present, in which case the old value was ignored
+    pub ignored: bool,
+}
+
+/// Load the config at `path` into `T` renaming the deprecated top-level keys,
+/// `aliases` being a list of `(old, new)` names.
+///
+/// Every deprecated key encountered is returned so that the caller can warn
+/// the user. When both the old and the new key are present the new one wins.
+pub fn load_with_aliases<T>(
+    path: impl AsRef<Path>,
+    aliases: &[(&str, &str)],
+) -> crate::Result<(T, Vec<DeprecatedKey>)>
+where
+    T: DeserializeOwned,
+{
+    let mut config = load_value(path)?;
+    let mut deprecated = Vec::new();
+
+    if let Value::Object(map) = &mut config {
+        for (old, new) in aliases {
+            let Some(value) = map.remove(*old) else {
+                continue;
+            };
+
+            let ignored = map.contains_key(*new);
+            if !ignored {
+                map.insert(new.to_string(), value);
+            }
+
+            deprecated.push(DeprecatedKey {
+                old: old.to_string(),
+                new: new.to_string(),
+                ignored,
+            });
+        }
+    }
+
+    Ok((from_value(config)?, deprecated))
+}
+
+/// Get the field names of the struct `T`, as seen by serde (renames included)
+pub(crate) fn struct_fields<'de, T>() -> &'static [&'static str]
+where
+    T: serde::Deserialize<'de>,
+{
+    let mut fields: &'static [&'static str] = &[];
+    let _ = T::deserialize(FieldsCollector(&mut fields));
+
+    fields
+}
+
+/// A deserializer which only records the fields requested by `deserialize_struct`
+struct FieldsCollector<'a>(&'a mut &'static [&'static str]);
+
+impl<'de> serde::Deserializer<'de> for FieldsCollector<'_> {
+    type Error = serde::de::value::Error;
+
+    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        Err(serde::de::Error::custom("not a struct"))
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: serde::de::Visitor<'de>,
+    {
+        *self.0 = fields;
+
+        Err(serde::de::Error::custom("fields collected"))
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf option unit unit_struct newtype_struct seq tuple
+        tuple_struct map enum identifier ignored_any
+    }
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[derive(Debug, PartialEq, serde::Deserialize)]
+    struct Logging {
+        level: String,
+    }
+
+    fn write_config(dir: &TempDir) -> std::path::PathBuf {
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "logging": { "level": "debug" }, "server": { "port": 80 } }"#,
+        )
+        .unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_load_section() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write_config(&dir);
+
+        let logging: Logging = load_section(&path, "logging").unwrap();
+        assert_eq!(logging.level, "debug");
+    }
+
+    #[test]
+    fn test_load_missing_section() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = write_config(&dir);
+
+        let err = load_section::<Logging>(&path, "metrics").unwrap_err();
+        assert!(matches!(&err, Error::SectionNotFound(section) if section == "metrics"));
+        assert_eq!(err.to_string(), "section not found: metrics");
+    }
+
+    #[test]
+    fn test_load_limited_depth() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, format!("{}{}", "[".repeat(64), "]".repeat(64))).unwrap();
+
+        let err = load_limited_depth::<Value>(&path, 16).unwrap_err();
+        assert!(matches!(
+            err,
+            Error::InvalidConfig("config nesting too deep")
+        ));
+
+        assert!(load_limited_depth::<Value>(&path, 64).is_ok());
+        assert_eq!(depth(&serde_json::json!({ "a": { "b": [1] }, "c": 2 })), 3);
+        assert_eq!(depth(&serde_json::json!(1)), 0);
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_load_limited_depth_toml() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.toml");
+        let too_deep = |err| matches!(err, Error::InvalidConfig("config nesting too deep"));
+
+        // would overflow the stack of the parser
+        let levels = 200_000;
+        let deep = [
+            format!("a = {}{}\n", "[".repeat(levels), "]".repeat(levels)),
+            format!("a = {}1{}\n", "{ b = ".repeat(levels), " }".repeat(levels)),
+            format!("{} = 1\n", vec!["a"; levels].join(".")),
+            format!("[{}]\nb = 1\n", vec!["a"; levels].join(".")),
+        ];
+
+        for content in deep {
+            std::fs::write(&path, content).unwrap();
+            assert!(too_deep(
+                load_limited_depth::<Value>(&path, 16).unwrap_err()
+            ));
+        }
+
+        std::fs::write(
+            &path,
+            "# [[[[ { \n\
+             name = \"[[[{{\" # ]\n\
+             literal = '[[[ \\'\n\
+             multi = \"\"\"\n[[[ \" {{{\n\"\"\"\n\
+             list = [[1, 2], [3.5]]\n\
+             [server.tls]\n\
+             options = { cert.path = \"a\" }\n",
+        )
+        .unwrap();
+        let config = load_limited_depth::<Value>(&path, 5).unwrap();
+        assert_eq!(config["server"]["tls"]["options"]["cert"]["path"], "a");
+        assert_eq!(depth(&config), 5);
+        assert!(too_deep(load_limited_depth::<Value>(&path, 4).unwrap_err()));
+
+        // the array of tables holds a table (4 levels along with the root)
+        assert_eq!(toml_depth("[[a.b]]\nc = 1\n"), 4);
+        assert_eq!(toml_depth("[[a.b]]\nc.d = [1]\n"), 6);
+    }
+
+    #[test]
+    fn test_load_case_insensitive() {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(
+            &path,
+            r#"{ "Foo": "mixed", "BAR": false, "baz": 3, "Baz": 4 }"#,
+        )
+        .unwrap();
+
+        let config: crate::test_utils::TestConfig = load_case_insensitive(&path).unwrap();
+
+        assert_eq!(config.foo, "mixed");
+        assert!(!config.bar);
+        // the exact match wins
+        assert_eq!(config.baz, 3);
+    }
+
+    mod aliases {
+        use super::*;
+        use crate::test_utils::TestConfig;
+
+        const ALIASES: &[(&str, &str)] = &[("name", "foo"), ("size", "baz")];
+
+        fn load(content: &str) -> (TestConfig, Vec<DeprecatedKey>) {
+            let dir = TempDir::new("test_config").unwrap();
+            let path = dir.path().join("config.json");
+            std::fs::write(&path, content).unwrap();
+
+            load_with_aliases(&path, ALIASES).unwrap()
+        }
+
+        #[test]
+        fn test_old_key_only() {
+            let (config, deprecated) = load(r#"{ "name": "old", "bar": true, "size": 1 }"#);
+
+            assert_eq!(config.foo, "old");
+            assert_eq!(config.baz, 1);
+            assert_eq!(
+                deprecated,
+                vec![
+                    DeprecatedKey {
+                        old: "name".to_string(),
+                        new: "foo".to_string(),
+                        ignored: false,
+                    },
+                    DeprecatedKey {
+                        old: "size".to_string(),
+                        new: "baz".to_string(),
+                        ignored: false,
+                    },
+                ]
+            );
+        }
+
+        #[test]
+        fn test_new_key_only() {
+            let (config, deprecated) = load(r#"{ "foo": "new", "bar": true, "baz": 2 }"#);
+
+            assert_eq!(config.foo, "new");
+            assert!(deprecated.is_empty());
+        }
+
+        #[test]
+        fn test_both_keys() {
+            let (config, deprecated) =
+                load(r#"{ "name": "old", "foo": "new", "bar": true, "baz": 2 }"#);
+
+            assert_eq!(config.foo, "new");
+            assert_eq!(
+                deprecated,
+                vec![DeprecatedKey {
+                    old: "name".to_string(),
+                    new: "foo".to_string(),
+                    ignored: true,
+                }]
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod getter_tests {
+    use super::*;
+    use serde_json::json;
+
+    fn config() -> Value {
+        json!({ "server": { "host": "localhost", "port": 8080, "tls": false }, "ratio": -1 })
+    }
+
+    #[test]
+    fn test_getters() {
+        let config = config();
+
+        assert_eq!(get_string(&config, "server.host").unwrap(), "localhost");
+        assert_eq!(get_u64(&config, "server.port").unwrap(), 8080);
+        assert!(!get_bool(&config, "server.tls").unwrap());
+        assert_eq!(get_path(&config, "server.port"), Some(&json!(8080)));
+    }
+
+    #[test]
+    fn test_type_mismatch() {
+        let config = config();
+
+        let err = get_u64(&config, "server.host").unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::InvalidType { key, expected: "an unsigned integer", found: "a string" }
+                if key == "server.host"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "invalid type for `server.host`: expected an unsigned integer, found a string"
+        );
+
+        assert!(matches!(
+            get_u64(&config, "ratio"),
+            Err(Error::InvalidType {
+                found: "a number",
+                ..
+            })
+        ));
+        assert!(matches!(
+            get_string(&config, "server"),
+            Err(Error::InvalidType {
+                found: "an object",
+                ..
+            })
+        ));
+        assert!(matches!(
+            get_bool(&config, "server.port"),
+            Err(Error::InvalidType { .. })
+        ));
+    }
+
+    #[test]
+    fn test_missing_path() {
+        let config = config();
+
+        for key in ["server.user", "client.host", "server.host.name", ""] {
+            let err = get_string(&config, key).unwrap_err();
+            assert!(
+                matches!(&err, Error::KeyNotFound(missing) if missing == key),
+                "{err}"
+            );
+        }
+
+        assert_eq!(
+            get_bool(&config, "server.http2").unwrap_err().to_string(),
+            "key not found: server.http2"
+        );
+    }
+}
+
+#[cfg(test)]
+mod patch_tests {
+    use super::*;
+    use crate::format::Format;
+    use serde_json::json;
+
+    #[test]
+    fn test_set_path() {
+        let mut config = json!({ "server": { "host": "localhost" }, "name": "app" });
+
+        set_path(&mut config, "server.port", json!(8080)).unwrap();
+        set_path(&mut config, "logging.level", json!("debug")).unwrap();
+        set_path(&mut config, "name", json!("renamed")).unwrap();
+
+        assert_eq!(
+            config,
+            json!({
+                "server": { "host": "localhost", "port": 8080 },
+                "logging": { "level": "debug" },
+                "name": "renamed",
+            })
+        );
+
+        assert!(matches!(
+            set_path(&mut config, "name.first", json!(1)),
+            Err(Error::InvalidConfig(_))
+        ));
+        assert!(matches!(
+            set_path(&mut config, "server..port", json!(1)),
+            Err(Error::InvalidConfig("invalid key"))
+        ));
+    }
+
+    #[cfg_attr(
+        not(any(feature = "json", feature = "yaml", feature = "toml")),
+        allow(dead_code)
+    )]
+    fn patch(format: Format, filename: &str, content: &str) -> Value {
+        let dir = tempdir::TempDir::new("test_config").unwrap();
+        let path = dir.path().join(filename);
+        std::fs::write(&path, content).unwrap();
+
+        patch_field(&path, "baz", json!(7), format).unwrap();
+        patch_field(&path, "server.port", json!(8080), format).unwrap();
+
+        format
+            .parse(&std::fs::read_to_string(&path).unwrap())
+            .unwrap()
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn test_patch_json() {
+        let config = patch(
+            Format::Json,
+            "config.json",
+            r#"{ "foo": "foo", "baz": 1, "unknown": [1] }"#,
+        );
+
+        assert_eq!(
+            config,
+            json!({ "foo": "foo", "baz": 7, "unknown": [1], "server": { "port": 8080 } })
+        );
+    }
+
+    #[cfg(feature = "yaml")]
+    #[test]
+    fn test_patch_yaml() {
+        let config = patch(
+            Format::Yaml,
+            "config.yaml",
+            "foo: foo\nbaz: 1\nunknown: [1]\n",
+        );
+
+        assert_eq!(
+            config,
+            json!({ "foo": "foo", "baz": 7, "unknown": [1], "server": { "port": 8080 } })
+        );
+    }
+
+    #[cfg(feature = "toml")]
+    #[test]
+    fn test_patch_toml() {
+        let config = patch(
+            Format::Toml,
+            "config.toml",
+            "foo = \"foo\"\nbaz = 1\n\n[abc]\nkey = true\n",
+        );
+
+        assert_eq!(
+            config,
+            json!({ "foo": "foo", "baz": 7, "abc": { "key": true }, "server": { "port": 8080 } })
+        );
+    }
+}
@@ -0,0 +1,126 @@
+/*!
+  This module contains the utilities to refuse configs
+  written by a newer version of the application.
+*/
+
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use std::path::Path;
+
+use crate::{error::Error, value};
+
+/// Key holding the schema version of a config
+pub const VERSION_KEY: &str = "version";
+
+/// Configs carrying the schema version they have been written with,
+/// in their top-level `version` field
+///
+/// ```
+/// use cli_config::version::Versioned;
+///
+/// #[derive(serde::Deserialize)]
+/// struct MyConfig {
+///     pub version: u64,
+///     pub name: String,
+/// }
+///
+/// impl Versioned for MyConfig {
+///     const VERSION: u64 = 2;
+/// }
+/// ```
+pub trait Versioned: DeserializeOwned {
+    /// The newest version this build of the application knows
+    const VERSION: u64;
+}
+
+/// Load the config at `path` refusing it with `Error::UnsupportedVersion`
+/// when its `version` is newer than `T::VERSION`, e.g. after a downgrade,
+/// instead of silently ignoring the keys it doesn't know.
+///
+/// A config without `version` is considered as old as possible.
+pub fn load_version_checked<T>(path: impl AsRef<Path>) -> crate::Result<T>
+where
+    T: Versioned,
+{
+    let config = value::load_value(path)?;
+
+    let version = match config.get(VERSION_KEY) {
+        None => 0,
+        Some(Value::Number(version)) => version
+            .as_u64()
+            .ok_or(Error::InvalidConfig("invalid config version"))?,
+        Some(_) => return Err(Error::InvalidConfig("invalid config version")),
+    };
+
+    if version > T::VERSION {
+        return Err(Error::UnsupportedVersion {
+            found: version,
+            supported: T::VERSION,
+        });
+    }
+
+    value::from_value(config)
+}
+
+#[cfg(feature = "json")]
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempdir::TempDir;
+
+    #[derive(Debug, serde::Deserialize)]
+    struct VersionedConfig {
+        #[serde(default)]
+        version: u64,
+        name: String,
+    }
+
+    impl Versioned for VersionedConfig {
+        const VERSION: u64 = 2;
+    }
+
+    fn load(content: &str) -> crate::Result<VersionedConfig> {
+        let dir = TempDir::new("test_config").unwrap();
+        let path = dir.path().join("config.json");
+        std::fs::write(&path, content).unwrap();
+
+        load_version_checked(&path)
+    }
+
+    #[test]
+    fn test_supported_version() {
+        let config = load(r#"{ "version": 2, "name": "current" }"#).unwrap();
+        assert_eq!(config.version, 2);
+
+        assert_eq!(
+            load(r#"{ "version": 1, "name": "old" }"#).unwrap().name,
+            "old"
+        );
+        assert_eq!(load(r#"{ "name": "legacy" }"#).unwrap().version, 0);
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let err = load(r#"{ "version": 3, "name": "future", "new_key": true }"#).unwrap_err();
+
+        assert!(matches!(
+            err,
+            Error::UnsupportedVersion {
+                found: 3,
+                supported: 2
+            }
+        ));
+        assert_eq!(
+            err.to_string(),
+            "config version 3 is newer than supported 2"
+        );
+    }
+
+    #[test]
+    fn test_invalid_version() {
+        assert!(matches!(
+            load(r#"{ "version": "3", "name": "future" }"#),
+            Err(Error::InvalidConfig("invalid config version"))
+        ));
+    }
+}